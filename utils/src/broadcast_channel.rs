@@ -22,4 +22,12 @@ impl<T: 'static + Clone + Send + Sync> UnboundedBroadcast<T> {
 
         Ok(())
     }
+
+    /// sends to every receiver still connected, dropping the senders of
+    /// those that hung up, and returns how many were sent to
+    pub fn send_pruned(&mut self, message: T) -> usize {
+        self.channels.retain(|c| c.send(message.clone()).is_ok());
+
+        self.channels.len()
+    }
 }  
\ No newline at end of file
@@ -24,8 +24,64 @@ pub enum UtilsError<'a> {
     InsufficientAccounts,
     #[error("serum market account is none")]
     MarketAccountISNone,
+    #[error("no serum market trades the pair")]
+    MarketNotFound,
+    #[error("amm account is none")]
+    AmmAccountIsNone,
     #[error("serum market open orders account is none")]
     OpenOrdersAccountIsNone,
     #[error("sysvar rent account is none")]
     RentAccountIsNone,
+    #[error("invalid serum {0} account data")]
+    InvalidDexAccount(&'a str),
+    #[error("order {0} rounds to zero lots")]
+    ZeroLots(&'a str),
+    #[error("{0} is not representable with integer lot sizes")]
+    InvalidLotSize(&'a str),
+    #[error("market has a zero lot size")]
+    ZeroLotSize,
+    #[error("failed to find a vault signer nonce")]
+    VaultSignerNonceNotFound,
+    #[error("invalid ui amount: {0}")]
+    InvalidUiAmount(&'a str),
+    #[error("{0} overflows")]
+    AmountOverflow(&'a str),
+    #[error("{0} token account has an unexpected mint")]
+    UnexpectedMint(&'a str),
+    #[error("order would trade against an order of the same owner")]
+    SelfTrade,
+    #[error("open orders account has no free slot")]
+    OpenOrdersFull,
+    #[error("order {0} not found")]
+    OrderNotFound(u128),
+    #[error("invalid quote ladder: {0}")]
+    InvalidLadder(&'a str),
+    #[error("unsupported raydium amm version {0}")]
+    UnsupportedAmmVersion(u8),
+    #[error("failed to find a usd priced pool for the base token")]
+    UsdPriceNotFound,
+    #[error("account is not owned by a known raydium amm program")]
+    UnknownAmmProgram,
+    #[error("amm account data is {0} bytes, expected {1}")]
+    InvalidAmmDataLength(usize, usize),
+    #[error("pool has insufficient liquidity")]
+    InsufficientLiquidity,
+    #[error("amm swap fee is invalid")]
+    InvalidSwapFee,
+    #[error("slippage of {0} bps exceeds 10000")]
+    InvalidSlippage(u64),
+    #[error("serum market does not belong to the amm")]
+    AmmMarketMismatch,
+    #[error("account is not owned by a known raydium farm program")]
+    UnknownFarmProgram,
+    #[error("farm account data is {0} bytes, expected {1}")]
+    InvalidFarmDataLength(usize, usize),
+    #[error("ledger does not belong to the farm")]
+    LedgerFarmMismatch,
+    #[error("farm pays {0} rewards, {1} reward prices given")]
+    FarmRewardCountMismatch(usize, usize),
+    #[error("order book level has invalid price {0}")]
+    InvalidLevelPrice(f64),
+    #[error("market order has invalid size {0}")]
+    InvalidOrderSize(f64),
 }
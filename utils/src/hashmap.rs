@@ -32,6 +32,11 @@ pub static PUBKEY_MAP: Lazy<HashMap<&str, Pubkey>> = Lazy::new(|| {
         Pubkey::from_str("SRMuApVNdxXokk5GT7XD5cUUgXMBCoAz2LHeuAoKWRt")
             .expect("failed to parse srm token mint"),
     );
+    m.insert(
+        "msrm_token_mint",
+        Pubkey::from_str("MSRMcoVyrFxnSgo5uXwone5SKcGhT1KEJMFEkMEWf9L")
+            .expect("failed to parse msrm token mint"),
+    );
     m.insert(
         "ray_usdc_lp_token_mint",
         Pubkey::from_str("BZFGfXMrjG2sS7QT2eiCDEevPFnkYYF7kzJpWfYxPbcx")
@@ -82,6 +87,26 @@ pub static PUBKEY_MAP: Lazy<HashMap<&str, Pubkey>> = Lazy::new(|| {
         Pubkey::from_str("9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin")
             .expect("failed to parse mainnet serum program id"),
     );
+    m.insert(
+        "raydium_amm_v3_program_id",
+        Pubkey::from_str("27haf8L6oxUeXrHrgEgsexjSY5hbVUWEmvv9Nyxg8vQv")
+            .expect("failed to parse raydium amm v3 program id"),
+    );
+    m.insert(
+        "raydium_amm_v4_program_id",
+        Pubkey::from_str("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8")
+            .expect("failed to parse raydium amm v4 program id"),
+    );
+    m.insert(
+        "raydium_staking_program_id",
+        Pubkey::from_str("EhhTKczWMGQt46ynNeRX1WfeagwwJd7ufHvCDjRxjo5Q")
+            .expect("failed to parse raydium staking program id"),
+    );
+    m.insert(
+        "raydium_fusion_program_id",
+        Pubkey::from_str("9KEPoZmtHUrBbhWN1v1KWLMkkvwY6WLtAVUCPRtRjP4z")
+            .expect("failed to parse raydium fusion program id"),
+    );
     m.insert(
         "ray_sol_open_orders",
         Pubkey::from_str("JQEY8R9frhxuvcsewGfgkCVdGWztpHLx4P9zmTAsZFM")
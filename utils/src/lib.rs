@@ -1,6 +1,7 @@
 pub mod crypto;
 pub mod errors;
 mod hashmap;
+mod pubkey_serde;
 pub mod raydium;
 pub mod serum;
 pub mod token_mints;
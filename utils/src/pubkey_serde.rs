@@ -0,0 +1,14 @@
+//! serde helpers that encode a Pubkey as its base58 string rather than as a
+//! byte array, for use with `#[serde(with = "crate::pubkey_serde")]`
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use serde::{de, Deserialize, Deserializer, Serializer};
+use std::str::FromStr;
+
+pub fn serialize<S: Serializer>(key: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&key.to_string())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+    let key = String::deserialize(deserializer)?;
+    Pubkey::from_str(&key).map_err(de::Error::custom)
+}
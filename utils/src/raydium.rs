@@ -1,34 +1,26 @@
 use crate::errors::UtilsError;
 use crate::serum;
-use crate::serum::mainnet_serum_program_id;
-use crate::serum::ray_sol_market;
-use crate::serum::ray_srm_market;
-use crate::serum::ray_usdc_market;
-use crate::serum::ray_usdt_market;
-use crate::serum::sol_usdc_market;
-use crate::serum::srm_usdc_market;
-use crate::serum::usdt_usdc_market;
+use crate::serum::units::{LotPrice, MarketSpec};
 use crate::token_mints::RAY_TOKEN_DECIMALS;
 use crate::token_mints::SRM_TOKEN_DECIMALS;
 use crate::token_mints::USDC_TOKEN_DECIMALS;
 use crate::token_mints::USDT_TOKEN_DECIMALS;
 use crate::token_mints::WSOL_TOKEN_DECIMALS;
-use anchor_client::{
-    solana_client::rpc_client::RpcClient,
-    solana_sdk::{account_info::IntoAccountInfo, program_pack::Pack, pubkey::Pubkey},
-};
-use anchor_lang::__private::bytemuck::cast_slice;
-use anyhow::{Result, anyhow};
+use anchor_client::{solana_client::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey};
+use anyhow::Result;
 use arrayref::{array_ref, array_refs};
-use serum_dex::matching::OrderBookState;
-use spl_token::amount_to_ui_amount;
-use spl_token::state::Account as TokenAccount;
-use spl_token::state::Mint as MintAccount;
-use std::convert::identity;
-use std::ops::DerefMut;
 use std::sync::Arc;
 
 use crate::hashmap::PUBKEY_MAP;
+use crate::raydium::reserves::PoolReserves;
+
+pub mod farm;
+pub mod farm_yield;
+pub mod instructions;
+pub mod liquidity;
+pub mod pool;
+pub mod reserves;
+pub mod swap;
 
 #[derive(Debug, Clone, Default)]
 pub struct AmmInfoLayoutV3 {
@@ -170,15 +162,27 @@ impl AMMs {
             AMMs::USDTUSDC => *PUBKEY_MAP.get("usdt_usdc_amm_id").unwrap(),
         }
     }
+    #[deprecated(note = "read the market from the amm account with AmmInfoLayout::serum_market")]
+    #[allow(deprecated)]
     pub fn serum_market(&self) -> Pubkey {
         match self {
-            AMMs::RAYSOL => ray_sol_market(),
-            AMMs::RAYSRM => ray_srm_market(),
-            AMMs::RAYUSDC => ray_usdc_market(),
-            AMMs::RAYUSDT => ray_usdt_market(),
-            AMMs::SOLUSDC => sol_usdc_market(),
-            AMMs::SRMUSDC => srm_usdc_market(),
-            AMMs::USDTUSDC => usdt_usdc_market(),
+            AMMs::RAYSOL => serum::ray_sol_market(),
+            AMMs::RAYSRM => serum::ray_srm_market(),
+            AMMs::RAYUSDC => serum::ray_usdc_market(),
+            AMMs::RAYUSDT => serum::ray_usdt_market(),
+            AMMs::SOLUSDC => serum::sol_usdc_market(),
+            AMMs::SRMUSDC => serum::srm_usdc_market(),
+            AMMs::USDTUSDC => serum::usdt_usdc_market(),
+        }
+    }
+    /// the pool pairing the base token with usdc, whose serum market prices
+    /// the base token in usd
+    fn usd_pool(self) -> AMMs {
+        match self {
+            AMMs::RAYSOL | AMMs::RAYSRM | AMMs::RAYUSDC | AMMs::RAYUSDT => AMMs::RAYUSDC,
+            AMMs::SOLUSDC => AMMs::SOLUSDC,
+            AMMs::SRMUSDC => AMMs::SRMUSDC,
+            AMMs::USDTUSDC => AMMs::USDTUSDC,
         }
     }
     pub fn base_token_decimals(&self) -> u8 {
@@ -201,397 +205,160 @@ impl AMMs {
     pub fn calculate_lp_token_price(self, rpc: &Arc<RpcClient>) -> Result<f64> {
         AMMs::lp_token_price(self, rpc)
     }
+    /// returns the usd price of the quote token, derived from the base token
+    /// usd price and the pool's serum market
     pub fn quote_token_usd_price(self, rpc: &Arc<RpcClient>) -> Result<f64> {
-        match self {
-            AMMs::USDTUSDC => {
-                let (open_orders, mut market_state) = serum::load_serum_open_orders_order_book_state(rpc, self.serum_market(), mainnet_serum_program_id(), self.open_orders())?;
-                let coin_lot_size = market_state.coin_lot_size;
-                let pc_lot_size = market_state.pc_lot_size;
-        
-                let asks_key = Pubkey::new(cast_slice(&identity(market_state.asks) as &[_]));
-                let bids_key = Pubkey::new(cast_slice(&identity(market_state.bids) as &[_]));
-                let mut account_datas = rpc.get_multiple_accounts(&[asks_key, bids_key])?;
-                let asks_acct = std::mem::take(&mut account_datas[0]);
-                let bids_acct = std::mem::take(&mut account_datas[1]);
-                if asks_acct.is_none() {
-                    return Err(UtilsError::AsksAccountIsNone.into());
-                }
-                if bids_acct.is_none() {
-                    return Err(UtilsError::BidsAccountIsNone.into());
-                }
-                let asks_acct = asks_acct.unwrap();
-                let bids_acct = bids_acct.unwrap();
-        
-                let mut asks_tuple = (asks_key, asks_acct);
-                let asks_account = asks_tuple.into_account_info();
-        
-                let mut bids_tuple = (bids_key, bids_acct);
-                let bids_account = bids_tuple.into_account_info();
-
-                let mut asks = market_state.load_asks_mut(&asks_account)?;
-                let mut bids = market_state.load_bids_mut(&bids_account)?;
-                let order_book_state = OrderBookState {
-                    market_state: &mut market_state,
-                    asks: &mut asks,
-                    bids: &mut bids,
-                };
-                let tick_size = serum::tick_size(
-                    coin_lot_size,
-                    pc_lot_size,
-                    self.base_token_decimals(),
-                    self.quote_token_decimals(),
-                );
-                let (asks_price, bids_price) = serum::find_best_ask_bid_price(&order_book_state)?;
-                let asks_price = asks_price as f64 * tick_size;
-                let _bids_price = bids_price as f64 * tick_size;
-                let quote_price = 1_f64 / asks_price;
-                
-                let base_usd_price = self.base_token_usd_price(rpc)?;
-                let quote_usd_price = quote_price * base_usd_price;
-                Ok(quote_usd_price)
-            },
-            _ => Err(anyhow!("quote token price not support for pair {:#?}", self).into()),
-        }
+        let base_usd_price = self.base_token_usd_price(rpc)?;
+        quote_token_usd_price(rpc, &self.pricing_keys(rpc)?, base_usd_price)
     }
+    /// returns the best ask of the serum market the base token's usdc pool
+    /// trades on, as recorded in that pool's amm account
     pub fn base_token_usd_price(self, rpc: &Arc<RpcClient>) -> Result<f64> {
-        match self {
-            // just fetch price for RAY_USDC
-            AMMs::RAYSOL | AMMs::RAYSRM | AMMs::RAYUSDC | AMMs::RAYUSDT => {
-                let mut market_state = serum::load_serum_market(
-                    rpc,
-                    serum::ray_usdc_market(),
-                    serum::mainnet_serum_program_id(),
-                )?;
-                let coin_lot_size = market_state.coin_lot_size;
-                let pc_lot_size = market_state.pc_lot_size;
-
-                let asks_key = Pubkey::new(cast_slice(&identity(market_state.asks) as &[_]));
-                let bids_key = Pubkey::new(cast_slice(&identity(market_state.bids) as &[_]));
-
-                let mut accounts = rpc.get_multiple_accounts(&[asks_key, bids_key])?;
-                let asks_acct = std::mem::take(&mut accounts[0]);
-                if asks_acct.is_none() {
-                    return Err(UtilsError::AsksAccountIsNone.into());
-                }
-                let asks_acct = asks_acct.unwrap();
-                let bids_acct = std::mem::take(&mut accounts[1]);
-                if bids_acct.is_none() {
-                    return Err(UtilsError::BidsAccountIsNone.into());
-                }
-                let bids_acct = bids_acct.unwrap();
-                let mut asks_tuple = (asks_key, asks_acct);
-                let asks_account = asks_tuple.into_account_info();
-
-                let mut bids_tuple = (bids_key, bids_acct);
-                let bids_account = bids_tuple.into_account_info();
-
-                let mut asks = market_state.load_asks_mut(&asks_account)?;
-                let mut bids = market_state.load_bids_mut(&bids_account)?;
-                let order_book_state = OrderBookState {
-                    market_state: &mut market_state,
-                    asks: &mut asks,
-                    bids: &mut bids,
-                };
-
-                let ray_usdc_tick_size = serum::tick_size(
-                    coin_lot_size,
-                    pc_lot_size,
-                    RAY_TOKEN_DECIMALS,
-                    USDC_TOKEN_DECIMALS,
-                );
-                let (asks_price, _bids_price) = serum::find_best_ask_bid_price(&order_book_state)?;
-                Ok(asks_price as f64 * ray_usdc_tick_size)
-            }
-            AMMs::USDTUSDC => {
-                let mut market_state = serum::load_serum_market(
-                    rpc,
-                    serum::usdt_usdc_market(),
-                    serum::mainnet_serum_program_id(),
-                )?;
-                let coin_lot_size = market_state.coin_lot_size;
-                let pc_lot_size = market_state.pc_lot_size;
-
-                let asks_key = Pubkey::new(cast_slice(&identity(market_state.asks) as &[_]));
-                let bids_key = Pubkey::new(cast_slice(&identity(market_state.bids) as &[_]));
-
-                let mut accounts = rpc.get_multiple_accounts(&[asks_key, bids_key])?;
-                let asks_acct = std::mem::take(&mut accounts[0]);
-                if asks_acct.is_none() {
-                    return Err(UtilsError::AsksAccountIsNone.into());
-                }
-                let asks_acct = asks_acct.unwrap();
-                let bids_acct = std::mem::take(&mut accounts[1]);
-                if bids_acct.is_none() {
-                    return Err(UtilsError::BidsAccountIsNone.into());
-                }
-                let bids_acct = bids_acct.unwrap();
-
-
-                let mut asks_tuple = (asks_key, asks_acct);
-                let asks_account = asks_tuple.into_account_info();
-
-                let mut bids_tuple = (bids_key, bids_acct);
-                let bids_account = bids_tuple.into_account_info();
-
-                let mut asks = market_state.load_asks_mut(&asks_account)?;
-                let mut bids = market_state.load_bids_mut(&bids_account)?;
-                let order_book_state = OrderBookState {
-                    market_state: &mut market_state,
-                    asks: &mut asks,
-                    bids: &mut bids,
-                };
-
-                let usdt_usdc_tick_size = serum::tick_size(
-                    coin_lot_size,
-                    pc_lot_size,
-                    USDT_TOKEN_DECIMALS,
-                    USDC_TOKEN_DECIMALS,
-                );
-                let (asks_price, _bids_price) = serum::find_best_ask_bid_price(&order_book_state)?;
-                Ok(asks_price as f64 * usdt_usdc_tick_size)
-            }
-            AMMs::SOLUSDC => {
-                let mut market_state = serum::load_serum_market(
-                    rpc,
-                    serum::sol_usdc_market(),
-                    serum::mainnet_serum_program_id(),
-                )?;
-                let coin_lot_size = market_state.coin_lot_size;
-                let pc_lot_size = market_state.pc_lot_size;
-
-                let asks_key = Pubkey::new(cast_slice(&identity(market_state.asks) as &[_]));
-                let bids_key = Pubkey::new(cast_slice(&identity(market_state.bids) as &[_]));
-
-                let mut accounts = rpc.get_multiple_accounts(&[asks_key, bids_key])?;
-                let asks_acct = std::mem::take(&mut accounts[0]);
-                if asks_acct.is_none() {
-                    return Err(UtilsError::AsksAccountIsNone.into());
-                }
-                let asks_acct = asks_acct.unwrap();
-                let bids_acct = std::mem::take(&mut accounts[1]);
-                if bids_acct.is_none() {
-                    return Err(UtilsError::BidsAccountIsNone.into());
-                }
-                let bids_acct = bids_acct.unwrap();
-
-
-                let mut asks_tuple = (asks_key, asks_acct);
-                let asks_account = asks_tuple.into_account_info();
+        let usd_pool = AmmInfoLayout::load(rpc, self.usd_pool().amm_id())?;
+        best_ask_price(
+            rpc,
+            usd_pool.serum_market(),
+            usd_pool.serum_program_id(),
+            self.base_token_decimals(),
+            USDC_TOKEN_DECIMALS,
+        )
+    }
+    /// reads the serum market the pool trades on from its amm account
+    fn pricing_keys(self, rpc: &Arc<RpcClient>) -> Result<PricingKeys> {
+        let layout = AmmInfoLayout::load(rpc, self.amm_id())?;
+        Ok(PricingKeys {
+            amm_id: self.amm_id(),
+            serum_market: layout.serum_market(),
+            serum_program_id: layout.serum_program_id(),
+            base_token_decimals: self.base_token_decimals(),
+            quote_token_decimals: self.quote_token_decimals(),
+        })
+    }
+    fn lp_token_price(token: AMMs, rpc: &Arc<RpcClient>) -> Result<f64> {
+        let base_usd_price = AMMs::base_token_usd_price(token, rpc)?;
+        pool_lp_token_price(rpc, &token.pricing_keys(rpc)?, base_usd_price)
+    }
+}
 
-                let mut bids_tuple = (bids_key, bids_acct);
-                let bids_account = bids_tuple.into_account_info();
+/// the accounts and decimals pricing a pool takes, whether the pool is one of
+/// the AMMs or was loaded from pool metadata
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PricingKeys {
+    pub amm_id: Pubkey,
+    pub serum_market: Pubkey,
+    pub serum_program_id: Pubkey,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+}
 
-                let mut asks = market_state.load_asks_mut(&asks_account)?;
-                let mut bids = market_state.load_bids_mut(&bids_account)?;
-                let order_book_state = OrderBookState {
-                    market_state: &mut market_state,
-                    asks: &mut asks,
-                    bids: &mut bids,
-                };
+/// returns the best ask of a serum market in quote tokens per base token
+pub(crate) fn best_ask_price(
+    rpc: &Arc<RpcClient>,
+    market: Pubkey,
+    serum_program_id: Pubkey,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Result<f64> {
+    let market_state = serum::load_serum_market(rpc, market, serum_program_id)?;
+    let spec = MarketSpec::from_market_state(
+        &market_state,
+        base_token_decimals,
+        quote_token_decimals,
+    );
+    let (asks_price, _bids_price) =
+        serum::with_order_book(rpc, market_state, |order_book_state| {
+            serum::find_best_ask_bid_price(order_book_state)
+        })?;
+    Ok(spec.lot_price_to_ui(LotPrice(asks_price))?.to_f64())
+}
 
-                let sol_usdc_tick_size = serum::tick_size(
-                    coin_lot_size,
-                    pc_lot_size,
-                    WSOL_TOKEN_DECIMALS,
-                    USDC_TOKEN_DECIMALS,
-                );
-                let (asks_price, _bids_price) = serum::find_best_ask_bid_price(&order_book_state)?;
-                Ok(asks_price as f64 * sol_usdc_tick_size)
-            }
-            AMMs::SRMUSDC => {
-                let mut market_state = serum::load_serum_market(
-                    rpc,
-                    serum::srm_usdc_market(),
-                    serum::mainnet_serum_program_id(),
-                )?;
-                let coin_lot_size = market_state.coin_lot_size;
-                let pc_lot_size = market_state.pc_lot_size;
+/// returns the usd price of a pool's quote token, given the usd price of its
+/// base token
+pub(crate) fn quote_token_usd_price(
+    rpc: &Arc<RpcClient>,
+    keys: &PricingKeys,
+    base_usd_price: f64,
+) -> Result<f64> {
+    let asks_price = best_ask_price(
+        rpc,
+        keys.serum_market,
+        keys.serum_program_id,
+        keys.base_token_decimals,
+        keys.quote_token_decimals,
+    )?;
+    let quote_price = 1_f64 / asks_price;
+    Ok(quote_price * base_usd_price)
+}
 
-                let asks_key = Pubkey::new(cast_slice(&identity(market_state.asks) as &[_]));
-                let bids_key = Pubkey::new(cast_slice(&identity(market_state.bids) as &[_]));
+/// prices a pool's lp token from its reserves, given the usd price of its
+/// base token
+pub(crate) fn pool_lp_token_price(
+    rpc: &Arc<RpcClient>,
+    keys: &PricingKeys,
+    base_usd_price: f64,
+) -> Result<f64> {
+    // the layout is picked from the amm account's owner and length
+    let reserves = PoolReserves::load(rpc, keys.amm_id)?;
+    let quote_usd_price = quote_token_usd_price(rpc, keys, base_usd_price)?;
+    Ok(reserves.lp_token_price(
+        keys.base_token_decimals,
+        keys.quote_token_decimals,
+        base_usd_price,
+        quote_usd_price,
+    ))
+}
 
-                let mut accounts = rpc.get_multiple_accounts(&[asks_key, bids_key])?;
-                let asks_acct = std::mem::take(&mut accounts[0]);
-                if asks_acct.is_none() {
-                    return Err(UtilsError::AsksAccountIsNone.into());
-                }
-                let asks_acct = asks_acct.unwrap();
-                let bids_acct = std::mem::take(&mut accounts[1]);
-                if bids_acct.is_none() {
-                    return Err(UtilsError::BidsAccountIsNone.into());
-                }
-                let bids_acct = bids_acct.unwrap();
+pub fn raydium_amm_v3_program_id() -> Pubkey {
+    *PUBKEY_MAP.get("raydium_amm_v3_program_id").unwrap()
+}
 
-                let mut asks_tuple = (asks_key, asks_acct);
-                let asks_account = asks_tuple.into_account_info();
+pub fn raydium_amm_v4_program_id() -> Pubkey {
+    *PUBKEY_MAP.get("raydium_amm_v4_program_id").unwrap()
+}
 
-                let mut bids_tuple = (bids_key, bids_acct);
-                let bids_account = bids_tuple.into_account_info();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmmLayouts {
+    V3,
+    V4,
+}
 
-                let mut asks = market_state.load_asks_mut(&asks_account)?;
-                let mut bids = market_state.load_bids_mut(&bids_account)?;
-                let order_book_state = OrderBookState {
-                    market_state: &mut market_state,
-                    asks: &mut asks,
-                    bids: &mut bids,
-                };
+impl AmmLayouts {
+    /// every known layout, newest last. a new amm version is supported by
+    /// adding its layout here along with its program id and account length
+    pub const ALL: [AmmLayouts; 2] = [AmmLayouts::V3, AmmLayouts::V4];
 
-                let srm_usdc_tick_size = serum::tick_size(
-                    coin_lot_size,
-                    pc_lot_size,
-                    SRM_TOKEN_DECIMALS,
-                    USDC_TOKEN_DECIMALS,
-                );
-                let (asks_price, _bids_price) = serum::find_best_ask_bid_price(&order_book_state)?;
-                Ok(asks_price as f64 * srm_usdc_tick_size)
-            }
+    /// the amm program owning accounts of this layout
+    pub fn program_id(self) -> Pubkey {
+        match self {
+            AmmLayouts::V3 => raydium_amm_v3_program_id(),
+            AmmLayouts::V4 => raydium_amm_v4_program_id(),
         }
     }
-    fn lp_token_price(token: AMMs, rpc: &Arc<RpcClient>) -> Result<f64> {
-        let serum_market = AMMs::serum_market(&token);
-        let (base_token_total, quote_token_total, mut market_state) = {
-            let (open_orders, market_state) =
-                serum::load_serum_open_orders_order_book_state(
-                    rpc,
-                    serum_market,
-                    serum::mainnet_serum_program_id(),
-                    AMMs::open_orders(token),
-                )?;
-            (
-                open_orders.native_coin_total,
-                open_orders.native_pc_total,
-                market_state,
-            )
-        };
-        let coin_lot_size = market_state.coin_lot_size;
-        let pc_lot_size = market_state.pc_lot_size;
-
-        let asks_key = Pubkey::new(cast_slice(&identity(market_state.asks) as &[_]));
-        let bids_key = Pubkey::new(cast_slice(&identity(market_state.bids) as &[_]));
-        let mut account_datas = rpc.get_multiple_accounts(&[asks_key, bids_key])?;
-        let asks_acct = std::mem::take(&mut account_datas[0]);
-        let bids_acct = std::mem::take(&mut account_datas[1]);
-        if asks_acct.is_none() {
-            return Err(UtilsError::AsksAccountIsNone.into());
-        }
-        if bids_acct.is_none() {
-            return Err(UtilsError::BidsAccountIsNone.into());
+    /// the length of an amm account of this layout
+    pub fn account_len(self) -> usize {
+        match self {
+            AmmLayouts::V3 => AmmInfoLayoutV3::LEN,
+            AmmLayouts::V4 => AmmInfoLayoutV4::LEN,
         }
-        let asks_acct = asks_acct.unwrap();
-        let bids_acct = bids_acct.unwrap();
-
-        let mut asks_tuple = (asks_key, asks_acct);
-        let asks_account = asks_tuple.into_account_info();
-
-        let mut bids_tuple = (bids_key, bids_acct);
-        let bids_account = bids_tuple.into_account_info();
-        let mut asks = market_state.load_asks_mut(&asks_account)?;
-        let mut bids = market_state.load_bids_mut(&bids_account)?;
-        let order_book_state = OrderBookState {
-            market_state: &mut market_state,
-            asks: &mut asks,
-            bids: &mut bids,
-        };
-        let (
-            need_take_pnl_coin, 
-            need_take_pnl_pc, 
-            lp_mint_address, 
-            pool_coin_token_account,
-            pool_pc_token_account,
-        ) = match AmmLayouts::from(token) {
-            AmmLayouts::V3 => {
-                let layout = AmmInfoLayoutV3::load(rpc, AMMs::amm_id(token))?;
-                (
-                    layout.need_take_pnl_coin,
-                    layout.need_take_pnl_pc,
-                    layout.lp_mint_address,
-                    layout.pool_coin_token_account,
-                    layout.pool_pc_token_account
-                )
-            },
-            AmmLayouts::V4 => {
-                let layout = AmmInfoLayoutV4::load(rpc, AMMs::amm_id(token))?;
-                (
-                    layout.need_take_pnl_coin,
-                    layout.need_take_pnl_pc,
-                    layout.lp_mint_address,
-                    layout.pool_coin_token_account,
-                    layout.pool_pc_token_account
-                )
-            }
+    }
+    pub fn from_program_id(program_id: &Pubkey) -> Option<AmmLayouts> {
+        AmmLayouts::ALL
+            .iter()
+            .copied()
+            .find(|layout| layout.program_id() == *program_id)
+    }
+    /// picks the layout of an amm account from its owner, checking that the
+    /// data has the length the layout expects
+    pub fn detect(owner: &Pubkey, data_len: usize) -> Result<AmmLayouts> {
+        let layout = match AmmLayouts::from_program_id(owner) {
+            Some(layout) => layout,
+            None => return Err(UtilsError::UnknownAmmProgram.into()),
         };
-        let tick_size = serum::tick_size(
-            coin_lot_size,
-            pc_lot_size,
-            AMMs::base_token_decimals(&token),
-            AMMs::quote_token_decimals(&token),
-        );
-
-        let (asks_price, bids_price) = serum::find_best_ask_bid_price(&order_book_state)?;
-        let asks_price = asks_price as f64 * tick_size;
-        let _bids_price = bids_price as f64 * tick_size;
-        let quote_price = 1_f64 / asks_price;
-
-        let base_usd_price = AMMs::base_token_usd_price(token, rpc)?;
-        let quote_usd_price = quote_price * base_usd_price;
-
-        let mut token_accounts = rpc.get_multiple_accounts(&[
-            lp_mint_address,
-            pool_coin_token_account,
-            pool_pc_token_account,
-        ])?;
-        if token_accounts.len() != 3 {
-            return Err(UtilsError::InsufficientAccounts.into());
-        }
-        let lp_mint_account = std::mem::take(&mut token_accounts[0]);
-        let pool_coin_token_account = std::mem::take(&mut token_accounts[1]);
-        let pool_pc_token_account = std::mem::take(&mut token_accounts[2]);
-        if lp_mint_account.is_none() {
-            return Err(UtilsError::TokenAccountISNone("failed to get lp token mint").into());
-        }
-        let lp_mint_account = lp_mint_account.unwrap();
-        if pool_coin_token_account.is_none() {
-            return Err(UtilsError::TokenAccountISNone("failed to get coin token account").into());
+        if data_len != layout.account_len() {
+            return Err(UtilsError::InvalidAmmDataLength(data_len, layout.account_len()).into());
         }
-        let pool_coin_token_account = pool_coin_token_account.unwrap();
-        if pool_pc_token_account.is_none() {
-            return Err(UtilsError::TokenAccountISNone("failed to get pc token account").into());
-        }
-        let pool_pc_token_account = pool_pc_token_account.unwrap();
-        let lp_mint_account = MintAccount::unpack_unchecked(&lp_mint_account.data[..])?;
-        let pool_coin_account =
-            TokenAccount::unpack_unchecked(&pool_coin_token_account.data[..])?;
-        let pool_pc_account =
-            TokenAccount::unpack_unchecked(&pool_pc_token_account.data[..])?;
-        let pool_pc_amount = pool_pc_account.amount;
-        let pool_pc_amount = pool_pc_amount + quote_token_total;
-        let pool_pc_amount = pool_pc_amount - need_take_pnl_pc;
-        let pool_pc_amount = pool_pc_amount as f64 * quote_usd_price;
-        let pool_pc_ui_amount =
-        amount_to_ui_amount(pool_pc_amount as u64, AMMs::quote_token_decimals(&token));
-
-        let pool_coin_amount = pool_coin_account.amount;
-        let pool_coin_amount = pool_coin_amount + base_token_total;
-        let pool_coin_amount = pool_coin_amount - need_take_pnl_coin;
-        let pool_coin_amount = pool_coin_amount as f64 * base_usd_price;
-        let pool_coin_ui_amount =
-            amount_to_ui_amount(pool_coin_amount as u64, AMMs::base_token_decimals(&token));        
-
-        let lp_token_supply_ui_amount =
-            amount_to_ui_amount(lp_mint_account.supply, lp_mint_account.decimals);
-
-        let lp_token_price =
-            (pool_coin_ui_amount + pool_pc_ui_amount) / lp_token_supply_ui_amount;
-
-        Ok(lp_token_price)
+        Ok(layout)
     }
-    
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum AmmLayouts {
-    V3,
-    V4,
 }
 
 impl From<AMMs> for AmmLayouts {
@@ -605,14 +372,23 @@ impl From<AMMs> for AmmLayouts {
 
 
 impl AmmInfoLayoutV3 {
+    pub const LEN: usize = 680;
+
     pub fn load(rpc: &Arc<RpcClient>, amm_key: Pubkey) -> Result<AmmInfoLayoutV3> {
         let account_data = rpc.get_account_data(&amm_key)?;
-        let layout = AmmInfoLayoutV3::unpack_from_slice(&account_data[..]);
-        Ok(layout)
+        AmmInfoLayoutV3::try_unpack_from_slice(&account_data[..])
     }
+    /// like unpack_from_slice, but returns an error rather than panicking when
+    /// the data is too short
+    pub fn try_unpack_from_slice(src: &[u8]) -> Result<AmmInfoLayoutV3> {
+        if src.len() < AmmInfoLayoutV3::LEN {
+            return Err(UtilsError::InvalidAmmDataLength(src.len(), AmmInfoLayoutV3::LEN).into());
+        }
+        Ok(AmmInfoLayoutV3::unpack_from_slice(src))
+    }
+    /// panics if `src` is shorter than LEN
     pub fn unpack_from_slice(src: &[u8]) -> AmmInfoLayoutV3 {
-        const LEN: usize = 680;
-        let input = array_ref![src, 0, LEN];
+        let input = array_ref![src, 0, AmmInfoLayoutV3::LEN];
         let (
             status,
             nonce,
@@ -738,14 +514,23 @@ impl AmmInfoLayoutV3 {
     }
 }
 impl AmmInfoLayoutV4 {
+    pub const LEN: usize = 752;
+
     pub fn load(rpc: &Arc<RpcClient>, amm_key: Pubkey) -> Result<AmmInfoLayoutV4> {
         let account_data = rpc.get_account_data(&amm_key)?;
-        let layout = AmmInfoLayoutV4::unpack_from_slice(&account_data[..]);
-        Ok(layout)
+        AmmInfoLayoutV4::try_unpack_from_slice(&account_data[..])
     }
+    /// like unpack_from_slice, but returns an error rather than panicking when
+    /// the data is too short
+    pub fn try_unpack_from_slice(src: &[u8]) -> Result<AmmInfoLayoutV4> {
+        if src.len() < AmmInfoLayoutV4::LEN {
+            return Err(UtilsError::InvalidAmmDataLength(src.len(), AmmInfoLayoutV4::LEN).into());
+        }
+        Ok(AmmInfoLayoutV4::unpack_from_slice(src))
+    }
+    /// panics if `src` is shorter than LEN
     pub fn unpack_from_slice(src: &[u8]) -> AmmInfoLayoutV4 {
-        const LEN: usize = 752;
-        let input = array_ref![src, 0, LEN];
+        let input = array_ref![src, 0, AmmInfoLayoutV4::LEN];
         let (
             status,
             nonce,
@@ -903,6 +688,105 @@ impl AmmInfoLayoutV4 {
     }
 }
 
+/// a decoded amm account of any supported layout
+#[derive(Debug, Clone)]
+pub enum AmmInfoLayout {
+    V3(AmmInfoLayoutV3),
+    V4(AmmInfoLayoutV4),
+}
+
+impl AmmInfoLayout {
+    /// loads an amm account, picking its layout from the owning program and
+    /// the data length
+    pub fn load(rpc: &Arc<RpcClient>, amm_key: Pubkey) -> Result<AmmInfoLayout> {
+        let account = rpc.get_account(&amm_key)?;
+        AmmInfoLayout::unpack(&account.owner, &account.data[..])
+    }
+    /// decodes the data of an amm account owned by `owner`
+    pub fn unpack(owner: &Pubkey, data: &[u8]) -> Result<AmmInfoLayout> {
+        match AmmLayouts::detect(owner, data.len())? {
+            AmmLayouts::V3 => Ok(AmmInfoLayout::V3(AmmInfoLayoutV3::try_unpack_from_slice(
+                data,
+            )?)),
+            AmmLayouts::V4 => Ok(AmmInfoLayout::V4(AmmInfoLayoutV4::try_unpack_from_slice(
+                data,
+            )?)),
+        }
+    }
+    pub fn layout(&self) -> AmmLayouts {
+        match self {
+            AmmInfoLayout::V3(_) => AmmLayouts::V3,
+            AmmInfoLayout::V4(_) => AmmLayouts::V4,
+        }
+    }
+    pub fn nonce(&self) -> u64 {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.nonce,
+            AmmInfoLayout::V4(layout) => layout.nonce,
+        }
+    }
+    pub fn need_take_pnl_coin(&self) -> u64 {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.need_take_pnl_coin,
+            AmmInfoLayout::V4(layout) => layout.need_take_pnl_coin,
+        }
+    }
+    pub fn need_take_pnl_pc(&self) -> u64 {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.need_take_pnl_pc,
+            AmmInfoLayout::V4(layout) => layout.need_take_pnl_pc,
+        }
+    }
+    pub fn pool_coin_token_account(&self) -> Pubkey {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.pool_coin_token_account,
+            AmmInfoLayout::V4(layout) => layout.pool_coin_token_account,
+        }
+    }
+    pub fn pool_pc_token_account(&self) -> Pubkey {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.pool_pc_token_account,
+            AmmInfoLayout::V4(layout) => layout.pool_pc_token_account,
+        }
+    }
+    pub fn coin_mint_address(&self) -> Pubkey {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.coin_mint_address,
+            AmmInfoLayout::V4(layout) => layout.coin_mint_address,
+        }
+    }
+    pub fn pc_mint_address(&self) -> Pubkey {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.pc_mint_address,
+            AmmInfoLayout::V4(layout) => layout.pc_mint_address,
+        }
+    }
+    pub fn lp_mint_address(&self) -> Pubkey {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.lp_mint_address,
+            AmmInfoLayout::V4(layout) => layout.lp_mint_address,
+        }
+    }
+    pub fn amm_open_orders(&self) -> Pubkey {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.amm_open_orders,
+            AmmInfoLayout::V4(layout) => layout.amm_open_orders,
+        }
+    }
+    pub fn serum_market(&self) -> Pubkey {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.serum_market,
+            AmmInfoLayout::V4(layout) => layout.serum_market,
+        }
+    }
+    pub fn serum_program_id(&self) -> Pubkey {
+        match self {
+            AmmInfoLayout::V3(layout) => layout.serum_program_id,
+            AmmInfoLayout::V4(layout) => layout.serum_program_id,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1011,6 +895,45 @@ mod test {
             .expect("failed to load layour");
     }
 
+    #[test]
+    pub fn test_amm_layout_detection() {
+        assert_eq!(
+            raydium_amm_v4_program_id().to_string(),
+            "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"
+        );
+        let mut data = vec![0_u8; AmmInfoLayoutV4::LEN];
+        // the nonce is the second u64 field and need_take_pnl_coin the 25th
+        data[8..16].copy_from_slice(&254_u64.to_le_bytes());
+        data[192..200].copy_from_slice(&7_u64.to_le_bytes());
+        let layout = AmmInfoLayout::unpack(&raydium_amm_v4_program_id(), &data).unwrap();
+        assert_eq!(layout.layout(), AmmLayouts::V4);
+        assert_eq!(layout.nonce(), 254);
+        assert_eq!(layout.need_take_pnl_coin(), 7);
+
+        // v4 data in a v3 account, short data and unknown owners are errors
+        assert!(AmmInfoLayout::unpack(&raydium_amm_v3_program_id(), &data).is_err());
+        assert!(AmmInfoLayout::unpack(&raydium_amm_v4_program_id(), &data[..100]).is_err());
+        assert!(AmmInfoLayout::unpack(&Pubkey::default(), &data).is_err());
+        assert!(AmmInfoLayoutV3::try_unpack_from_slice(&data[..100]).is_err());
+        let data = vec![0_u8; AmmInfoLayoutV3::LEN];
+        let layout = AmmInfoLayout::unpack(&raydium_amm_v3_program_id(), &data).unwrap();
+        assert_eq!(layout.layout(), AmmLayouts::V3);
+    }
+
+    #[test]
+    pub fn test_load_amm_info_layouts() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        for amm in [AMMs::RAYSOL, AMMs::RAYUSDT, AMMs::SOLUSDC].iter() {
+            let layout = AmmInfoLayout::load(&rpc, amm.amm_id()).expect("failed to load layout");
+            assert_eq!(layout.layout(), AmmLayouts::from(*amm));
+            assert_eq!(layout.amm_open_orders(), amm.open_orders());
+        }
+    }
+
     #[test]
     pub fn test_sol_usdc_base_token_price() {
         let cluster = Cluster::Custom(
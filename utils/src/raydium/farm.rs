@@ -0,0 +1,488 @@
+use crate::errors::UtilsError;
+use crate::hashmap::PUBKEY_MAP;
+use anchor_client::{
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{program_pack::Pack, pubkey::Pubkey},
+};
+use anyhow::Result;
+use arrayref::{array_ref, array_refs};
+use spl_token::state::Account as TokenAccount;
+use std::sync::Arc;
+
+/// scale of the reward per share accumulator of staking pools
+pub const STAKING_REWARD_PRECISION: u128 = 1_000_000_000;
+/// scale of the reward per share accumulators of fusion pools
+pub const FUSION_REWARD_PRECISION: u128 = 1_000_000_000_000_000;
+
+pub fn raydium_staking_program_id() -> Pubkey {
+    *PUBKEY_MAP.get("raydium_staking_program_id").unwrap()
+}
+
+pub fn raydium_fusion_program_id() -> Pubkey {
+    *PUBKEY_MAP.get("raydium_fusion_program_id").unwrap()
+}
+
+/// a single reward farm of the staking program. blocks are slots
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StakingPoolLayout {
+    pub state: u64,
+    pub nonce: u64,
+    pub pool_lp_token_account: Pubkey,
+    pub pool_reward_token_account: Pubkey,
+    pub owner: Pubkey,
+    pub fee_owner: Pubkey,
+    pub fee_y: u64,
+    pub fee_x: u64,
+    pub total_reward: u64,
+    /// rewards accrued per staked lp token up to last_block, scaled by
+    /// STAKING_REWARD_PRECISION
+    pub reward_per_share_net: u128,
+    pub last_block: u64,
+    pub reward_per_block: u64,
+}
+
+impl StakingPoolLayout {
+    pub const LEN: usize = 200;
+
+    pub fn load(rpc: &Arc<RpcClient>, farm_key: Pubkey) -> Result<StakingPoolLayout> {
+        let account_data = rpc.get_account_data(&farm_key)?;
+        StakingPoolLayout::try_unpack_from_slice(&account_data[..])
+    }
+    pub fn try_unpack_from_slice(src: &[u8]) -> Result<StakingPoolLayout> {
+        check_len(src, StakingPoolLayout::LEN)?;
+        let input = array_ref![src, 0, StakingPoolLayout::LEN];
+        let (
+            state,
+            nonce,
+            pool_lp_token_account,
+            pool_reward_token_account,
+            owner,
+            fee_owner,
+            fee_y,
+            fee_x,
+            total_reward,
+            reward_per_share_net,
+            last_block,
+            reward_per_block,
+        ) = array_refs![input, 8, 8, 32, 32, 32, 32, 8, 8, 8, 16, 8, 8];
+        Ok(StakingPoolLayout {
+            state: u64::from_le_bytes(*state),
+            nonce: u64::from_le_bytes(*nonce),
+            pool_lp_token_account: Pubkey::new_from_array(*pool_lp_token_account),
+            pool_reward_token_account: Pubkey::new_from_array(*pool_reward_token_account),
+            owner: Pubkey::new_from_array(*owner),
+            fee_owner: Pubkey::new_from_array(*fee_owner),
+            fee_y: u64::from_le_bytes(*fee_y),
+            fee_x: u64::from_le_bytes(*fee_x),
+            total_reward: u64::from_le_bytes(*total_reward),
+            reward_per_share_net: u128::from_le_bytes(*reward_per_share_net),
+            last_block: u64::from_le_bytes(*last_block),
+            reward_per_block: u64::from_le_bytes(*reward_per_block),
+        })
+    }
+    /// returns the reward harvestable by `ledger` at `slot`, with `staked_lp`
+    /// being the balance of the pool lp token account
+    pub fn pending_reward(
+        &self,
+        ledger: &StakingLedgerLayout,
+        staked_lp: u64,
+        slot: u64,
+    ) -> Result<u64> {
+        let per_share = accrued_per_share(
+            self.reward_per_share_net,
+            self.reward_per_block,
+            self.last_block,
+            slot,
+            staked_lp,
+            STAKING_REWARD_PRECISION,
+        )?;
+        pending_reward(
+            ledger.deposit_balance,
+            per_share,
+            ledger.reward_debt,
+            STAKING_REWARD_PRECISION,
+        )
+    }
+}
+
+/// a dual reward farm of the fusion program. blocks are slots
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FusionPoolLayout {
+    pub state: u64,
+    pub nonce: u64,
+    pub pool_lp_token_account: Pubkey,
+    pub pool_reward_token_account: Pubkey,
+    pub total_reward: u64,
+    /// rewards accrued per staked lp token up to last_block, scaled by
+    /// FUSION_REWARD_PRECISION
+    pub per_share: u128,
+    pub per_block: u64,
+    pub option: u8,
+    pub pool_reward_token_account_b: Pubkey,
+    pub total_reward_b: u64,
+    pub per_share_b: u128,
+    pub per_block_b: u64,
+    pub last_block: u64,
+    pub owner: Pubkey,
+}
+
+impl FusionPoolLayout {
+    pub const LEN: usize = 224;
+
+    pub fn load(rpc: &Arc<RpcClient>, farm_key: Pubkey) -> Result<FusionPoolLayout> {
+        let account_data = rpc.get_account_data(&farm_key)?;
+        FusionPoolLayout::try_unpack_from_slice(&account_data[..])
+    }
+    pub fn try_unpack_from_slice(src: &[u8]) -> Result<FusionPoolLayout> {
+        check_len(src, FusionPoolLayout::LEN)?;
+        let input = array_ref![src, 0, FusionPoolLayout::LEN];
+        let (
+            state,
+            nonce,
+            pool_lp_token_account,
+            pool_reward_token_account,
+            total_reward,
+            per_share,
+            per_block,
+            option,
+            pool_reward_token_account_b,
+            _padding,
+            total_reward_b,
+            per_share_b,
+            per_block_b,
+            last_block,
+            owner,
+        ) = array_refs![input, 8, 8, 32, 32, 8, 16, 8, 1, 32, 7, 8, 16, 8, 8, 32];
+        Ok(FusionPoolLayout {
+            state: u64::from_le_bytes(*state),
+            nonce: u64::from_le_bytes(*nonce),
+            pool_lp_token_account: Pubkey::new_from_array(*pool_lp_token_account),
+            pool_reward_token_account: Pubkey::new_from_array(*pool_reward_token_account),
+            total_reward: u64::from_le_bytes(*total_reward),
+            per_share: u128::from_le_bytes(*per_share),
+            per_block: u64::from_le_bytes(*per_block),
+            option: option[0],
+            pool_reward_token_account_b: Pubkey::new_from_array(*pool_reward_token_account_b),
+            total_reward_b: u64::from_le_bytes(*total_reward_b),
+            per_share_b: u128::from_le_bytes(*per_share_b),
+            per_block_b: u64::from_le_bytes(*per_block_b),
+            last_block: u64::from_le_bytes(*last_block),
+            owner: Pubkey::new_from_array(*owner),
+        })
+    }
+    /// returns the rewards harvestable by `ledger` at `slot`, with
+    /// `staked_lp` being the balance of the pool lp token account
+    pub fn pending_rewards(
+        &self,
+        ledger: &FusionLedgerLayout,
+        staked_lp: u64,
+        slot: u64,
+    ) -> Result<(u64, u64)> {
+        let per_share = accrued_per_share(
+            self.per_share,
+            self.per_block,
+            self.last_block,
+            slot,
+            staked_lp,
+            FUSION_REWARD_PRECISION,
+        )?;
+        let per_share_b = accrued_per_share(
+            self.per_share_b,
+            self.per_block_b,
+            self.last_block,
+            slot,
+            staked_lp,
+            FUSION_REWARD_PRECISION,
+        )?;
+        Ok((
+            pending_reward(
+                ledger.deposit_balance,
+                per_share,
+                ledger.reward_debt,
+                FUSION_REWARD_PRECISION,
+            )?,
+            pending_reward(
+                ledger.deposit_balance,
+                per_share_b,
+                ledger.reward_debt_b,
+                FUSION_REWARD_PRECISION,
+            )?,
+        ))
+    }
+}
+
+/// a user's deposit in a staking pool
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StakingLedgerLayout {
+    pub state: u64,
+    pub pool_id: Pubkey,
+    pub staker_owner: Pubkey,
+    pub deposit_balance: u64,
+    pub reward_debt: u64,
+}
+
+impl StakingLedgerLayout {
+    pub const LEN: usize = 88;
+
+    pub fn load(rpc: &Arc<RpcClient>, ledger_key: Pubkey) -> Result<StakingLedgerLayout> {
+        let account_data = rpc.get_account_data(&ledger_key)?;
+        StakingLedgerLayout::try_unpack_from_slice(&account_data[..])
+    }
+    pub fn try_unpack_from_slice(src: &[u8]) -> Result<StakingLedgerLayout> {
+        check_len(src, StakingLedgerLayout::LEN)?;
+        let input = array_ref![src, 0, StakingLedgerLayout::LEN];
+        let (state, pool_id, staker_owner, deposit_balance, reward_debt) =
+            array_refs![input, 8, 32, 32, 8, 8];
+        Ok(StakingLedgerLayout {
+            state: u64::from_le_bytes(*state),
+            pool_id: Pubkey::new_from_array(*pool_id),
+            staker_owner: Pubkey::new_from_array(*staker_owner),
+            deposit_balance: u64::from_le_bytes(*deposit_balance),
+            reward_debt: u64::from_le_bytes(*reward_debt),
+        })
+    }
+}
+
+/// a user's deposit in a fusion pool
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FusionLedgerLayout {
+    pub state: u64,
+    pub pool_id: Pubkey,
+    pub staker_owner: Pubkey,
+    pub deposit_balance: u64,
+    pub reward_debt: u64,
+    pub reward_debt_b: u64,
+}
+
+impl FusionLedgerLayout {
+    pub const LEN: usize = 96;
+
+    pub fn load(rpc: &Arc<RpcClient>, ledger_key: Pubkey) -> Result<FusionLedgerLayout> {
+        let account_data = rpc.get_account_data(&ledger_key)?;
+        FusionLedgerLayout::try_unpack_from_slice(&account_data[..])
+    }
+    pub fn try_unpack_from_slice(src: &[u8]) -> Result<FusionLedgerLayout> {
+        check_len(src, FusionLedgerLayout::LEN)?;
+        let input = array_ref![src, 0, FusionLedgerLayout::LEN];
+        let (state, pool_id, staker_owner, deposit_balance, reward_debt, reward_debt_b) =
+            array_refs![input, 8, 32, 32, 8, 8, 8];
+        Ok(FusionLedgerLayout {
+            state: u64::from_le_bytes(*state),
+            pool_id: Pubkey::new_from_array(*pool_id),
+            staker_owner: Pubkey::new_from_array(*staker_owner),
+            deposit_balance: u64::from_le_bytes(*deposit_balance),
+            reward_debt: u64::from_le_bytes(*reward_debt),
+            reward_debt_b: u64::from_le_bytes(*reward_debt_b),
+        })
+    }
+}
+
+/// a farm of either program, picked from the account owner
+#[derive(Debug, Clone, PartialEq)]
+pub enum FarmLayout {
+    Staking(StakingPoolLayout),
+    Fusion(FusionPoolLayout),
+}
+
+impl FarmLayout {
+    pub fn load(rpc: &Arc<RpcClient>, farm_key: Pubkey) -> Result<FarmLayout> {
+        let account = rpc.get_account(&farm_key)?;
+        FarmLayout::unpack(&account.owner, &account.data[..])
+    }
+    pub fn unpack(owner: &Pubkey, data: &[u8]) -> Result<FarmLayout> {
+        if *owner == raydium_staking_program_id() {
+            Ok(FarmLayout::Staking(
+                StakingPoolLayout::try_unpack_from_slice(data)?,
+            ))
+        } else if *owner == raydium_fusion_program_id() {
+            Ok(FarmLayout::Fusion(FusionPoolLayout::try_unpack_from_slice(
+                data,
+            )?))
+        } else {
+            Err(UtilsError::UnknownFarmProgram.into())
+        }
+    }
+    pub fn pool_lp_token_account(&self) -> Pubkey {
+        match self {
+            FarmLayout::Staking(farm) => farm.pool_lp_token_account,
+            FarmLayout::Fusion(farm) => farm.pool_lp_token_account,
+        }
+    }
+    /// the native amount of each reward paid out per slot, in the order
+    /// the farm stores them
+    pub fn rewards_per_slot(&self) -> Vec<u64> {
+        match self {
+            FarmLayout::Staking(farm) => vec![farm.reward_per_block],
+            FarmLayout::Fusion(farm) => vec![farm.per_block, farm.per_block_b],
+        }
+    }
+}
+
+/// rewards a ledger can harvest, in native amounts of the reward tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PendingRewards {
+    pub reward: u64,
+    /// the second reward of fusion pools, zero for staking pools
+    pub reward_b: u64,
+}
+
+/// loads a farm, a ledger deposited in it, the staked lp and the current
+/// slot, and returns what the ledger could harvest right now
+pub fn load_pending_rewards(
+    rpc: &Arc<RpcClient>,
+    farm_key: Pubkey,
+    ledger_key: Pubkey,
+) -> Result<PendingRewards> {
+    let farm = FarmLayout::load(rpc, farm_key)?;
+    let lp_account_data = rpc.get_account_data(&farm.pool_lp_token_account())?;
+    let staked_lp = TokenAccount::unpack_unchecked(&lp_account_data[..])?.amount;
+    let slot = rpc.get_slot()?;
+    match farm {
+        FarmLayout::Staking(farm) => {
+            let ledger = StakingLedgerLayout::load(rpc, ledger_key)?;
+            if ledger.pool_id != farm_key {
+                return Err(UtilsError::LedgerFarmMismatch.into());
+            }
+            Ok(PendingRewards {
+                reward: farm.pending_reward(&ledger, staked_lp, slot)?,
+                reward_b: 0,
+            })
+        }
+        FarmLayout::Fusion(farm) => {
+            let ledger = FusionLedgerLayout::load(rpc, ledger_key)?;
+            if ledger.pool_id != farm_key {
+                return Err(UtilsError::LedgerFarmMismatch.into());
+            }
+            let (reward, reward_b) = farm.pending_rewards(&ledger, staked_lp, slot)?;
+            Ok(PendingRewards { reward, reward_b })
+        }
+    }
+}
+
+fn check_len(src: &[u8], len: usize) -> Result<()> {
+    if src.len() < len {
+        return Err(UtilsError::InvalidFarmDataLength(src.len(), len).into());
+    }
+    Ok(())
+}
+
+/// brings a per share accumulator from `last_block` up to `slot`, the way
+/// the farm programs do on every deposit and withdrawal
+fn accrued_per_share(
+    per_share: u128,
+    per_block: u64,
+    last_block: u64,
+    slot: u64,
+    staked_lp: u64,
+    precision: u128,
+) -> Result<u128> {
+    if staked_lp == 0 || slot <= last_block {
+        return Ok(per_share);
+    }
+    let accrued = ((slot - last_block) as u128 * per_block as u128)
+        .checked_mul(precision)
+        .ok_or(UtilsError::AmountOverflow("farm reward"))?
+        / staked_lp as u128;
+    per_share
+        .checked_add(accrued)
+        .ok_or_else(|| UtilsError::AmountOverflow("farm reward").into())
+}
+
+fn pending_reward(deposit: u64, per_share: u128, reward_debt: u64, precision: u128) -> Result<u64> {
+    let earned = (deposit as u128)
+        .checked_mul(per_share)
+        .ok_or(UtilsError::AmountOverflow("farm reward"))?
+        / precision;
+    let pending = earned.saturating_sub(reward_debt as u128);
+    if pending > u64::MAX as u128 {
+        return Err(UtilsError::AmountOverflow("farm reward").into());
+    }
+    Ok(pending as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anchor_client::Cluster;
+    use std::str::FromStr;
+
+    #[test]
+    pub fn test_unpack_farm_layouts() {
+        let mut data = vec![0_u8; FusionPoolLayout::LEN];
+        data[0] = 1;
+        data[16..48].copy_from_slice(&[7_u8; 32]);
+        // per_share_b after the option byte, reward vault b, padding and
+        // total_reward_b
+        data[160..176].copy_from_slice(&42_u128.to_le_bytes());
+        data[184..192].copy_from_slice(&9_u64.to_le_bytes());
+        let farm = FarmLayout::unpack(&raydium_fusion_program_id(), &data).unwrap();
+        match farm {
+            FarmLayout::Fusion(farm) => {
+                assert_eq!(farm.state, 1);
+                assert_eq!(farm.pool_lp_token_account, Pubkey::new_from_array([7; 32]));
+                assert_eq!(farm.per_share_b, 42);
+                assert_eq!(farm.last_block, 9);
+            }
+            FarmLayout::Staking(_) => panic!("expected a fusion farm"),
+        }
+        assert!(FarmLayout::unpack(&raydium_staking_program_id(), &data[..100]).is_err());
+        assert!(FarmLayout::unpack(&Pubkey::default(), &data).is_err());
+
+        let mut data = vec![0_u8; StakingLedgerLayout::LEN];
+        data[72..80].copy_from_slice(&500_u64.to_le_bytes());
+        data[80..88].copy_from_slice(&3_u64.to_le_bytes());
+        let ledger = StakingLedgerLayout::try_unpack_from_slice(&data).unwrap();
+        assert_eq!(ledger.deposit_balance, 500);
+        assert_eq!(ledger.reward_debt, 3);
+    }
+
+    #[test]
+    pub fn test_pending_rewards() {
+        let farm = StakingPoolLayout {
+            reward_per_share_net: 2 * STAKING_REWARD_PRECISION,
+            last_block: 100,
+            reward_per_block: 1_000,
+            ..StakingPoolLayout::default()
+        };
+        let ledger = StakingLedgerLayout {
+            deposit_balance: 100,
+            reward_debt: 50,
+            ..StakingLedgerLayout::default()
+        };
+        // 10 slots of 1_000 shared by 1_000 staked lp adds 10 per share
+        assert_eq!(farm.pending_reward(&ledger, 1_000, 110).unwrap(), 1_150);
+        assert_eq!(farm.pending_reward(&ledger, 1_000, 90).unwrap(), 150);
+        assert_eq!(farm.pending_reward(&ledger, 0, 110).unwrap(), 150);
+
+        let farm = FusionPoolLayout {
+            per_share: FUSION_REWARD_PRECISION / 2,
+            per_block: 30,
+            per_share_b: 0,
+            per_block_b: 3,
+            last_block: 10,
+            ..FusionPoolLayout::default()
+        };
+        let ledger = FusionLedgerLayout {
+            deposit_balance: 200,
+            reward_debt: 100,
+            reward_debt_b: 0,
+            ..FusionLedgerLayout::default()
+        };
+        // 20 slots shared by 600 staked lp adds 1 and 0.1 per share
+        assert_eq!(farm.pending_rewards(&ledger, 600, 30).unwrap(), (200, 20));
+    }
+
+    #[test]
+    pub fn test_load_staking_pool() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        // the ray staking pool
+        let farm_key = Pubkey::from_str("4EwbZo8BZXP5313z5A2H11MRBP15M5n6YxfmkjXESKAW").unwrap();
+        let farm = FarmLayout::load(&rpc, farm_key).unwrap();
+        assert!(matches!(farm, FarmLayout::Staking(_)));
+        println!("{:#?}", farm);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::errors::UtilsError;
+use crate::raydium::farm::FarmLayout;
+use crate::raydium::reserves::PoolReserves;
+use crate::raydium::{quote_token_usd_price, AMMs};
+use anchor_client::{
+    solana_client::{rpc_client::RpcClient, rpc_response::RpcPerfSample},
+    solana_sdk::{program_pack::Pack, pubkey::Pubkey},
+};
+use anyhow::Result;
+use spl_token::amount_to_ui_amount;
+use spl_token::state::Account as TokenAccount;
+use std::sync::Arc;
+
+/// slots in a year at the nominal 400ms slot time, the fallback when the
+/// cluster reports no performance samples
+pub const SLOTS_PER_YEAR: f64 = 78_840_000_f64;
+const SECONDS_PER_YEAR: f64 = 31_536_000_f64;
+/// performance samples, each covering about a minute, the slot rate is
+/// averaged over
+const PERFORMANCE_SAMPLES: usize = 60;
+
+/// estimates the slots in a year from the cluster's recent slot rate
+pub fn slots_per_year(rpc: &Arc<RpcClient>) -> Result<f64> {
+    let samples = rpc.get_recent_performance_samples(Some(PERFORMANCE_SAMPLES))?;
+    Ok(slots_per_year_from_samples(&samples))
+}
+
+fn slots_per_year_from_samples(samples: &[RpcPerfSample]) -> f64 {
+    let slots: u64 = samples.iter().map(|sample| sample.num_slots).sum();
+    let seconds: u64 = samples
+        .iter()
+        .map(|sample| sample.sample_period_secs as u64)
+        .sum();
+    if slots == 0 || seconds == 0 {
+        return SLOTS_PER_YEAR;
+    }
+    slots as f64 / seconds as f64 * SECONDS_PER_YEAR
+}
+
+/// one reward a farm pays out every slot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FarmReward {
+    /// native amount of the reward token paid per slot
+    pub per_slot: u64,
+    pub decimals: u8,
+    pub usd_price: f64,
+}
+
+impl FarmReward {
+    pub fn usd_per_year(&self, slots_per_year: f64) -> f64 {
+        amount_to_ui_amount(self.per_slot, self.decimals) * slots_per_year * self.usd_price
+    }
+}
+
+/// what staking in a farm yields, in usd
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FarmYield {
+    /// usd value of the lp tokens staked in the farm
+    pub tvl_usd: f64,
+    /// usd value of every reward paid out over a year
+    pub reward_usd_per_year: f64,
+    /// yearly rewards as a fraction of tvl, zero for an empty farm
+    pub apr: f64,
+}
+
+impl FarmYield {
+    pub fn new(
+        rewards: &[FarmReward],
+        slots_per_year: f64,
+        staked_lp: u64,
+        lp_decimals: u8,
+        lp_usd_price: f64,
+    ) -> FarmYield {
+        let tvl_usd = amount_to_ui_amount(staked_lp, lp_decimals) * lp_usd_price;
+        let reward_usd_per_year = rewards
+            .iter()
+            .map(|reward| reward.usd_per_year(slots_per_year))
+            .sum();
+        let apr = if tvl_usd > 0_f64 {
+            reward_usd_per_year / tvl_usd
+        } else {
+            0_f64
+        };
+        FarmYield {
+            tvl_usd,
+            reward_usd_per_year,
+            apr,
+        }
+    }
+    /// returns the yearly yield when rewards are harvested and restaked
+    /// `compounds_per_year` times, the apr when they never are
+    pub fn apy(&self, compounds_per_year: u32) -> f64 {
+        if compounds_per_year == 0 {
+            return self.apr;
+        }
+        let n = compounds_per_year as f64;
+        (1_f64 + self.apr / n).powf(n) - 1_f64
+    }
+    /// loads the yield of a farm staking the lp token of `amm`. the lp token
+    /// is priced from the pool reserves, each reward with the base token usd
+    /// price of the matching entry of `reward_pools`, whose base token must
+    /// be that reward, and the year from the cluster's recent slot rate
+    pub fn load(
+        rpc: &Arc<RpcClient>,
+        farm_key: Pubkey,
+        amm: AMMs,
+        reward_pools: &[AMMs],
+    ) -> Result<FarmYield> {
+        let farm = FarmLayout::load(rpc, farm_key)?;
+        let rewards_per_slot = farm.rewards_per_slot();
+        if rewards_per_slot.len() != reward_pools.len() {
+            return Err(UtilsError::FarmRewardCountMismatch(
+                rewards_per_slot.len(),
+                reward_pools.len(),
+            )
+            .into());
+        }
+        let rewards = rewards_per_slot
+            .iter()
+            .zip(reward_pools)
+            .map(|(per_slot, pool)| {
+                Ok(FarmReward {
+                    per_slot: *per_slot,
+                    decimals: pool.base_token_decimals(),
+                    usd_price: pool.base_token_usd_price(rpc)?,
+                })
+            })
+            .collect::<Result<Vec<FarmReward>>>()?;
+        let lp_account_data = rpc.get_account_data(&farm.pool_lp_token_account())?;
+        let staked_lp = TokenAccount::unpack_unchecked(&lp_account_data[..])?.amount;
+        let reserves = PoolReserves::load(rpc, amm.amm_id())?;
+        let base_usd_price = amm.base_token_usd_price(rpc)?;
+        let quote_usd_price = quote_token_usd_price(rpc, &amm.pricing_keys(rpc)?, base_usd_price)?;
+        let lp_usd_price = reserves.lp_token_price(
+            amm.base_token_decimals(),
+            amm.quote_token_decimals(),
+            base_usd_price,
+            quote_usd_price,
+        );
+        Ok(FarmYield::new(
+            &rewards,
+            slots_per_year(rpc)?,
+            staked_lp,
+            reserves.lp_decimals,
+            lp_usd_price,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_farm_yield() {
+        let ray = FarmReward {
+            per_slot: 100_000,
+            decimals: 6,
+            usd_price: 2_f64,
+        };
+        // 0.1 ray a slot at 2 usd is 15_768_000 usd a year
+        assert!((ray.usd_per_year(SLOTS_PER_YEAR) - 15_768_000_f64).abs() < 1e-3);
+        // 1_000_000 lp tokens at 7.884 usd
+        let farm_yield = FarmYield::new(&[ray, ray], SLOTS_PER_YEAR, 1_000_000_000_000, 6, 7.884);
+        assert!((farm_yield.tvl_usd - 7_884_000_f64).abs() < 1e-3);
+        assert!((farm_yield.apr - 4_f64).abs() < 1e-9);
+        assert_eq!(farm_yield.apy(0), farm_yield.apr);
+        assert!((farm_yield.apy(1) - 4_f64).abs() < 1e-9);
+        // daily compounding beats the apr, continuous compounding bounds it
+        let daily = farm_yield.apy(365);
+        assert!(daily > 4_f64 && daily < 4_f64.exp() - 1_f64);
+        assert_eq!(
+            FarmYield::new(&[ray], SLOTS_PER_YEAR, 0, 6, 7.884).apr,
+            0_f64
+        );
+    }
+
+    #[test]
+    pub fn test_slots_per_year_from_samples() {
+        let sample = |num_slots: u64, sample_period_secs: u16| RpcPerfSample {
+            slot: 0,
+            num_transactions: 0,
+            num_slots,
+            sample_period_secs,
+        };
+        // 2 slots a second
+        let samples = [sample(120, 60), sample(120, 60)];
+        assert!((slots_per_year_from_samples(&samples) - 63_072_000_f64).abs() < 1e-3);
+        assert_eq!(slots_per_year_from_samples(&[]), SLOTS_PER_YEAR);
+        assert_eq!(slots_per_year_from_samples(&[sample(0, 0)]), SLOTS_PER_YEAR);
+    }
+}
@@ -0,0 +1,370 @@
+use crate::errors::UtilsError;
+use crate::raydium::liquidity::{DepositQuote, WithdrawQuote};
+use crate::raydium::{raydium_amm_v4_program_id, AmmInfoLayoutV4};
+use crate::serum::instructions::MarketAccounts;
+use anchor_client::solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use anyhow::Result;
+use serum_dex::state::MarketState;
+
+/// seed the amm authority is derived from, along with the amm nonce
+pub const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+
+const DEPOSIT_TAG: u8 = 3;
+const WITHDRAW_TAG: u8 = 4;
+const SWAP_BASE_IN_TAG: u8 = 9;
+const SWAP_BASE_OUT_TAG: u8 = 11;
+
+/// the accounts of a v4 amm and of its serum market that instructions are
+/// derived from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmmAccounts {
+    pub program_id: Pubkey,
+    pub amm_id: Pubkey,
+    pub authority: Pubkey,
+    pub open_orders: Pubkey,
+    pub target_orders: Pubkey,
+    pub pool_coin_token_account: Pubkey,
+    pub pool_pc_token_account: Pubkey,
+    pub lp_mint: Pubkey,
+    pub withdraw_queue: Pubkey,
+    pub temp_lp_token_account: Pubkey,
+    pub market: MarketAccounts,
+}
+
+impl AmmAccounts {
+    /// derives the amm authority and the market accounts from a loaded amm
+    /// and the serum market it trades on
+    pub fn new(
+        amm_id: Pubkey,
+        layout: &AmmInfoLayoutV4,
+        market_state: &MarketState,
+    ) -> Result<AmmAccounts> {
+        let program_id = raydium_amm_v4_program_id();
+        let market = MarketAccounts::new(
+            market_state,
+            layout.serum_program_id,
+            layout.coin_decimals as u8,
+            layout.pc_decimals as u8,
+        )?;
+        if market.market != layout.serum_market {
+            return Err(UtilsError::AmmMarketMismatch.into());
+        }
+        Ok(AmmAccounts {
+            program_id,
+            amm_id,
+            authority: amm_authority(&program_id, layout.nonce)?,
+            open_orders: layout.amm_open_orders,
+            target_orders: layout.amm_target_orders,
+            pool_coin_token_account: layout.pool_coin_token_account,
+            pool_pc_token_account: layout.pool_pc_token_account,
+            lp_mint: layout.lp_mint_address,
+            withdraw_queue: layout.pool_withdraw_queue,
+            temp_lp_token_account: layout.pool_temp_lp_token_account,
+            market,
+        })
+    }
+}
+
+/// derives the authority that owns the vaults of an amm
+pub fn amm_authority(program_id: &Pubkey, nonce: u64) -> Result<Pubkey> {
+    Ok(Pubkey::create_program_address(
+        &[AMM_AUTHORITY_SEED, &[nonce as u8]],
+        program_id,
+    )?)
+}
+
+/// builds a swap_base_in instruction, swapping exactly `amount_in` from
+/// `user_source` and failing if less than `minimum_amount_out` would reach
+/// `user_destination`
+pub fn swap_base_in(
+    amm: &AmmAccounts,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    user_owner: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    Instruction {
+        program_id: amm.program_id,
+        accounts: swap_accounts(amm, user_source, user_destination, user_owner),
+        data: swap_data(SWAP_BASE_IN_TAG, amount_in, minimum_amount_out),
+    }
+}
+
+/// builds a swap_base_out instruction, swapping exactly `amount_out` into
+/// `user_destination` and failing if more than `max_amount_in` would be
+/// taken from `user_source`
+pub fn swap_base_out(
+    amm: &AmmAccounts,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    user_owner: &Pubkey,
+    max_amount_in: u64,
+    amount_out: u64,
+) -> Instruction {
+    Instruction {
+        program_id: amm.program_id,
+        accounts: swap_accounts(amm, user_source, user_destination, user_owner),
+        data: swap_data(SWAP_BASE_OUT_TAG, max_amount_in, amount_out),
+    }
+}
+
+/// builds a deposit instruction with the coin side fixed. the amm takes
+/// exactly the quoted coin and the pc matching it at the pool ratio, failing
+/// if that is more than the quote's max_pc_amount
+pub fn deposit(
+    amm: &AmmAccounts,
+    user_coin_token_account: &Pubkey,
+    user_pc_token_account: &Pubkey,
+    user_lp_token_account: &Pubkey,
+    user_owner: &Pubkey,
+    quote: &DepositQuote,
+) -> Instruction {
+    // base side 0 fixes the coin amount
+    let mut data = Vec::with_capacity(25);
+    data.push(DEPOSIT_TAG);
+    data.extend_from_slice(&quote.max_coin_amount.to_le_bytes());
+    data.extend_from_slice(&quote.max_pc_amount.to_le_bytes());
+    data.extend_from_slice(&0_u64.to_le_bytes());
+    Instruction {
+        program_id: amm.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(amm.amm_id, false),
+            AccountMeta::new_readonly(amm.authority, false),
+            AccountMeta::new_readonly(amm.open_orders, false),
+            AccountMeta::new(amm.target_orders, false),
+            AccountMeta::new(amm.lp_mint, false),
+            AccountMeta::new(amm.pool_coin_token_account, false),
+            AccountMeta::new(amm.pool_pc_token_account, false),
+            AccountMeta::new_readonly(amm.market.market, false),
+            AccountMeta::new(*user_coin_token_account, false),
+            AccountMeta::new(*user_pc_token_account, false),
+            AccountMeta::new(*user_lp_token_account, false),
+            AccountMeta::new_readonly(*user_owner, true),
+            AccountMeta::new_readonly(amm.market.event_queue, false),
+        ],
+        data,
+    }
+}
+
+/// builds a withdraw instruction burning the quoted lp tokens, failing if
+/// less than the quote's min_coin_amount or min_pc_amount would be returned
+pub fn withdraw(
+    amm: &AmmAccounts,
+    user_lp_token_account: &Pubkey,
+    user_coin_token_account: &Pubkey,
+    user_pc_token_account: &Pubkey,
+    user_owner: &Pubkey,
+    quote: &WithdrawQuote,
+) -> Instruction {
+    let mut data = Vec::with_capacity(25);
+    data.push(WITHDRAW_TAG);
+    data.extend_from_slice(&quote.lp_amount.to_le_bytes());
+    data.extend_from_slice(&quote.min_coin_amount.to_le_bytes());
+    data.extend_from_slice(&quote.min_pc_amount.to_le_bytes());
+    Instruction {
+        program_id: amm.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(amm.amm_id, false),
+            AccountMeta::new_readonly(amm.authority, false),
+            AccountMeta::new(amm.open_orders, false),
+            AccountMeta::new(amm.target_orders, false),
+            AccountMeta::new(amm.lp_mint, false),
+            AccountMeta::new(amm.pool_coin_token_account, false),
+            AccountMeta::new(amm.pool_pc_token_account, false),
+            AccountMeta::new(amm.withdraw_queue, false),
+            AccountMeta::new(amm.temp_lp_token_account, false),
+            AccountMeta::new_readonly(amm.market.program_id, false),
+            AccountMeta::new(amm.market.market, false),
+            AccountMeta::new(amm.market.coin_vault, false),
+            AccountMeta::new(amm.market.pc_vault, false),
+            AccountMeta::new_readonly(amm.market.vault_signer, false),
+            AccountMeta::new(*user_lp_token_account, false),
+            AccountMeta::new(*user_coin_token_account, false),
+            AccountMeta::new(*user_pc_token_account, false),
+            AccountMeta::new_readonly(*user_owner, true),
+            AccountMeta::new(amm.market.event_queue, false),
+            AccountMeta::new(amm.market.bids, false),
+            AccountMeta::new(amm.market.asks, false),
+        ],
+        data,
+    }
+}
+
+fn swap_accounts(
+    amm: &AmmAccounts,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    user_owner: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(amm.amm_id, false),
+        AccountMeta::new_readonly(amm.authority, false),
+        AccountMeta::new(amm.open_orders, false),
+        AccountMeta::new(amm.target_orders, false),
+        AccountMeta::new(amm.pool_coin_token_account, false),
+        AccountMeta::new(amm.pool_pc_token_account, false),
+        AccountMeta::new_readonly(amm.market.program_id, false),
+        AccountMeta::new(amm.market.market, false),
+        AccountMeta::new(amm.market.bids, false),
+        AccountMeta::new(amm.market.asks, false),
+        AccountMeta::new(amm.market.event_queue, false),
+        AccountMeta::new(amm.market.coin_vault, false),
+        AccountMeta::new(amm.market.pc_vault, false),
+        AccountMeta::new_readonly(amm.market.vault_signer, false),
+        AccountMeta::new(*user_source, false),
+        AccountMeta::new(*user_destination, false),
+        AccountMeta::new_readonly(*user_owner, true),
+    ]
+}
+
+fn swap_data(tag: u8, first: u64, second: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(17);
+    data.push(tag);
+    data.extend_from_slice(&first.to_le_bytes());
+    data.extend_from_slice(&second.to_le_bytes());
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::raydium::AMMs;
+    use crate::serum;
+    use anchor_client::solana_client::rpc_client::RpcClient;
+    use anchor_client::Cluster;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    fn amm() -> AmmAccounts {
+        AmmAccounts {
+            program_id: raydium_amm_v4_program_id(),
+            amm_id: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            open_orders: Pubkey::new_unique(),
+            target_orders: Pubkey::new_unique(),
+            pool_coin_token_account: Pubkey::new_unique(),
+            pool_pc_token_account: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            withdraw_queue: Pubkey::new_unique(),
+            temp_lp_token_account: Pubkey::new_unique(),
+            market: MarketAccounts {
+                program_id: Pubkey::new_unique(),
+                market: Pubkey::new_unique(),
+                request_queue: Pubkey::new_unique(),
+                event_queue: Pubkey::new_unique(),
+                bids: Pubkey::new_unique(),
+                asks: Pubkey::new_unique(),
+                coin_mint: Pubkey::new_unique(),
+                pc_mint: Pubkey::new_unique(),
+                coin_vault: Pubkey::new_unique(),
+                pc_vault: Pubkey::new_unique(),
+                vault_signer: Pubkey::new_unique(),
+                coin_lot_size: 100_000_000,
+                pc_lot_size: 100,
+                base_token_decimals: 9,
+                quote_token_decimals: 6,
+            },
+        }
+    }
+
+    #[test]
+    pub fn test_swap_instructions() {
+        let amm = amm();
+        let (source, destination, owner) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let instruction = swap_base_in(&amm, &source, &destination, &owner, 1_000, 990);
+        assert_eq!(instruction.program_id, raydium_amm_v4_program_id());
+        assert_eq!(instruction.accounts.len(), 18);
+        assert_eq!(instruction.accounts[1].pubkey, amm.amm_id);
+        assert_eq!(instruction.accounts[14].pubkey, amm.market.vault_signer);
+        assert!(!instruction.accounts[14].is_writable);
+        assert_eq!(instruction.accounts[17].pubkey, owner);
+        assert!(instruction.accounts[17].is_signer);
+        assert_eq!(
+            instruction
+                .accounts
+                .iter()
+                .filter(|account| account.is_signer)
+                .count(),
+            1
+        );
+        assert_eq!(instruction.data[0], 9);
+        assert_eq!(instruction.data[1..9], 1_000_u64.to_le_bytes());
+        assert_eq!(instruction.data[9..17], 990_u64.to_le_bytes());
+
+        let instruction = swap_base_out(&amm, &source, &destination, &owner, 1_010, 1_000);
+        assert_eq!(instruction.data.len(), 17);
+        assert_eq!(instruction.data[0], 11);
+        assert_eq!(instruction.data[1..9], 1_010_u64.to_le_bytes());
+    }
+
+    #[test]
+    pub fn test_liquidity_instructions() {
+        let amm = amm();
+        let (coin, pc, lp, owner) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let quote = DepositQuote {
+            coin_amount: 1_000,
+            pc_amount: 50_000,
+            lp_amount: 7_000,
+            max_coin_amount: 1_000,
+            max_pc_amount: 50_500,
+        };
+        let instruction = deposit(&amm, &coin, &pc, &lp, &owner, &quote);
+        assert_eq!(instruction.accounts.len(), 14);
+        assert_eq!(instruction.accounts[5].pubkey, amm.lp_mint);
+        assert!(instruction.accounts[12].is_signer);
+        assert_eq!(instruction.data.len(), 25);
+        assert_eq!(instruction.data[0], 3);
+        assert_eq!(instruction.data[9..17], 50_500_u64.to_le_bytes());
+        assert_eq!(instruction.data[17..25], 0_u64.to_le_bytes());
+
+        let quote = WithdrawQuote {
+            lp_amount: 700,
+            coin_amount: 100,
+            pc_amount: 5_000,
+            min_coin_amount: 99,
+            min_pc_amount: 4_950,
+        };
+        let instruction = withdraw(&amm, &lp, &coin, &pc, &owner, &quote);
+        assert_eq!(instruction.accounts.len(), 22);
+        assert_eq!(instruction.accounts[9].pubkey, amm.temp_lp_token_account);
+        assert_eq!(instruction.accounts[15].pubkey, lp);
+        assert!(instruction.accounts[18].is_signer);
+        assert_eq!(instruction.data[0], 4);
+        assert_eq!(instruction.data[1..9], 700_u64.to_le_bytes());
+        assert_eq!(instruction.data[17..25], 4_950_u64.to_le_bytes());
+    }
+
+    #[test]
+    pub fn test_load_amm_accounts() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let layout = AmmInfoLayoutV4::load(&rpc, AMMs::SOLUSDC.amm_id()).unwrap();
+        let market_state =
+            serum::load_serum_market(&rpc, layout.serum_market, layout.serum_program_id).unwrap();
+        let amm = AmmAccounts::new(AMMs::SOLUSDC.amm_id(), &layout, &market_state).unwrap();
+        assert_eq!(
+            amm.authority,
+            Pubkey::from_str("5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1").unwrap()
+        );
+        assert_eq!(amm.market.market, layout.serum_market);
+    }
+}
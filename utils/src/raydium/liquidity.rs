@@ -0,0 +1,172 @@
+use crate::errors::UtilsError;
+use crate::raydium::reserves::PoolReserves;
+use crate::raydium::swap::{ceil_div, check_slippage, to_u64};
+use crate::raydium::{AmmInfoLayout, AmmInfoLayoutV4};
+use anchor_client::{solana_client::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey};
+use anyhow::Result;
+use std::sync::Arc;
+
+/// the amounts of a deposit that fixes the coin side, in native amounts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepositQuote {
+    pub coin_amount: u64,
+    /// pc matching coin_amount at the current pool ratio, rounded up as the
+    /// amm does
+    pub pc_amount: u64,
+    /// lp tokens minted for the deposit
+    pub lp_amount: u64,
+    /// the coin the deposit instruction may take, which is exactly
+    /// coin_amount since the coin side is fixed
+    pub max_coin_amount: u64,
+    /// the pc the deposit instruction may take once slippage is allowed for
+    pub max_pc_amount: u64,
+}
+
+/// the amounts returned for burning lp tokens, in native amounts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawQuote {
+    pub lp_amount: u64,
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    /// the least coin the withdrawal may return once slippage is allowed for
+    pub min_coin_amount: u64,
+    /// the least pc the withdrawal may return once slippage is allowed for
+    pub min_pc_amount: u64,
+}
+
+/// a v4 pool along with the reserves and lp supply deposits and withdrawals
+/// are priced against
+#[derive(Debug, Clone)]
+pub struct LiquidityPool {
+    pub layout: AmmInfoLayoutV4,
+    pub reserves: PoolReserves,
+}
+
+impl LiquidityPool {
+    /// loads the amm account and its reserves
+    pub fn load(rpc: &Arc<RpcClient>, amm_key: Pubkey) -> Result<LiquidityPool> {
+        let layout = AmmInfoLayoutV4::load(rpc, amm_key)?;
+        let reserves =
+            PoolReserves::load_with_layout(rpc, amm_key, &AmmInfoLayout::V4(layout.clone()))?;
+        Ok(LiquidityPool { layout, reserves })
+    }
+    /// quotes depositing exactly `coin_amount` along with the pc matching it
+    /// at the current pool ratio
+    pub fn quote_deposit(&self, coin_amount: u64, slippage_bps: u64) -> Result<DepositQuote> {
+        check_slippage(slippage_bps)?;
+        self.check_liquidity()?;
+        let coin_reserve = self.reserves.coin_amount as u128;
+        let pc_amount = to_u64(
+            ceil_div(
+                coin_amount as u128 * self.reserves.pc_amount as u128,
+                coin_reserve,
+            ),
+            "deposit pc amount",
+        )?;
+        let lp_amount = to_u64(
+            coin_amount as u128 * self.reserves.lp_supply as u128 / coin_reserve,
+            "deposit lp amount",
+        )?;
+        let max_pc_amount = to_u64(
+            ceil_div(pc_amount as u128 * (10_000 + slippage_bps as u128), 10_000),
+            "deposit pc amount",
+        )?;
+        Ok(DepositQuote {
+            coin_amount,
+            pc_amount,
+            lp_amount,
+            max_coin_amount: coin_amount,
+            max_pc_amount,
+        })
+    }
+    /// quotes burning `lp_amount` lp tokens for their share of the reserves
+    pub fn quote_withdraw(&self, lp_amount: u64, slippage_bps: u64) -> Result<WithdrawQuote> {
+        check_slippage(slippage_bps)?;
+        self.check_liquidity()?;
+        if lp_amount > self.reserves.lp_supply {
+            return Err(UtilsError::InsufficientLiquidity.into());
+        }
+        let share = |reserve: u64| {
+            (lp_amount as u128 * reserve as u128 / self.reserves.lp_supply as u128) as u64
+        };
+        let min_amount = |amount: u64| {
+            (amount as u128 * (10_000 - slippage_bps as u128) / 10_000) as u64
+        };
+        let coin_amount = share(self.reserves.coin_amount);
+        let pc_amount = share(self.reserves.pc_amount);
+        Ok(WithdrawQuote {
+            lp_amount,
+            coin_amount,
+            pc_amount,
+            min_coin_amount: min_amount(coin_amount),
+            min_pc_amount: min_amount(pc_amount),
+        })
+    }
+    fn check_liquidity(&self) -> Result<()> {
+        if self.reserves.coin_amount == 0
+            || self.reserves.pc_amount == 0
+            || self.reserves.lp_supply == 0
+        {
+            return Err(UtilsError::InsufficientLiquidity.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::raydium::AMMs;
+    use anchor_client::Cluster;
+
+    fn pool() -> LiquidityPool {
+        LiquidityPool {
+            layout: AmmInfoLayoutV4::default(),
+            reserves: PoolReserves {
+                coin_amount: 1_000_000_000,
+                pc_amount: 50_000_000_001,
+                lp_supply: 7_000_000_000,
+                ..PoolReserves::default()
+            },
+        }
+    }
+
+    #[test]
+    pub fn test_quote_deposit() {
+        let quote = pool().quote_deposit(10_000_000, 100).unwrap();
+        // 500_000_000.01 rounded up
+        assert_eq!(quote.pc_amount, 500_000_001);
+        assert_eq!(quote.lp_amount, 70_000_000);
+        assert_eq!(quote.max_coin_amount, 10_000_000);
+        assert_eq!(quote.max_pc_amount, 505_000_002);
+        let mut empty = pool();
+        empty.reserves.lp_supply = 0;
+        assert!(empty.quote_deposit(1, 0).is_err());
+    }
+
+    #[test]
+    pub fn test_quote_withdraw() {
+        let quote = pool().quote_withdraw(70_000_000, 50).unwrap();
+        assert_eq!(quote.coin_amount, 10_000_000);
+        // 500_000_000.01 rounded down
+        assert_eq!(quote.pc_amount, 500_000_000);
+        assert_eq!(quote.min_coin_amount, 9_950_000);
+        assert_eq!(quote.min_pc_amount, 497_500_000);
+        assert!(pool().quote_withdraw(7_000_000_001, 0).is_err());
+        assert!(pool().quote_withdraw(70_000_000, 10_001).is_err());
+    }
+
+    #[test]
+    pub fn test_load_liquidity_pool() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let pool = LiquidityPool::load(&rpc, AMMs::SOLUSDC.amm_id()).unwrap();
+        let deposit = pool.quote_deposit(1_000_000_000, 50).unwrap();
+        let withdraw = pool.quote_withdraw(deposit.lp_amount, 50).unwrap();
+        assert!(withdraw.coin_amount <= deposit.coin_amount);
+        println!("{:#?} {:#?}", deposit, withdraw);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::errors::UtilsError;
+use crate::raydium::{
+    best_ask_price, pool_lp_token_price, quote_token_usd_price, AmmLayouts, PricingKeys,
+};
+use crate::token_mints::{usdc_token_mint, usdt_token_mint};
+use anchor_client::{solana_client::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+/// the accounts of a raydium amm pool and its serum market, in the format of
+/// raydium's published liquidity pool list. base and quote are what the rest
+/// of the crate calls coin and pc
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RaydiumPool {
+    /// the amm account
+    #[serde(with = "crate::pubkey_serde")]
+    pub id: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub base_mint: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub quote_mint: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub lp_mint: Pubkey,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    pub lp_decimals: u8,
+    /// amm program version, which decides the layout of the amm account
+    pub version: u8,
+    #[serde(with = "crate::pubkey_serde")]
+    pub program_id: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub authority: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub open_orders: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub target_orders: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub base_vault: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub quote_vault: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub withdraw_queue: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub lp_vault: Pubkey,
+    pub market_version: u8,
+    #[serde(with = "crate::pubkey_serde")]
+    pub market_program_id: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub market_id: Pubkey,
+    /// the vault signer of the serum market
+    #[serde(with = "crate::pubkey_serde")]
+    pub market_authority: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub market_base_vault: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub market_quote_vault: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub market_bids: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub market_asks: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub market_event_queue: Pubkey,
+}
+
+impl RaydiumPool {
+    /// returns the layout of the pool's amm account
+    pub fn layout(&self) -> Result<AmmLayouts> {
+        match self.version {
+            3 => Ok(AmmLayouts::V3),
+            4 => Ok(AmmLayouts::V4),
+            version => Err(UtilsError::UnsupportedAmmVersion(version).into()),
+        }
+    }
+    /// returns the best ask of the pool's serum market in quote tokens per
+    /// base token
+    pub fn market_price(&self, rpc: &Arc<RpcClient>) -> Result<f64> {
+        best_ask_price(
+            rpc,
+            self.market_id,
+            self.market_program_id,
+            self.base_decimals,
+            self.quote_decimals,
+        )
+    }
+    /// returns the usd price of the quote token, given the usd price of the
+    /// base token
+    pub fn quote_token_usd_price(&self, rpc: &Arc<RpcClient>, base_usd_price: f64) -> Result<f64> {
+        quote_token_usd_price(rpc, &self.pricing_keys(), base_usd_price)
+    }
+    /// returns the usd price of the pool's lp token, given the usd price of
+    /// the base token
+    pub fn lp_token_price(&self, rpc: &Arc<RpcClient>, base_usd_price: f64) -> Result<f64> {
+        pool_lp_token_price(rpc, &self.pricing_keys(), base_usd_price)
+    }
+    fn pricing_keys(&self) -> PricingKeys {
+        PricingKeys {
+            amm_id: self.id,
+            serum_market: self.market_id,
+            serum_program_id: self.market_program_id,
+            base_token_decimals: self.base_decimals,
+            quote_token_decimals: self.quote_decimals,
+        }
+    }
+}
+
+/// raydium publishes its pools split into official and unofficial ones,
+/// while our own config files are a plain list
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PoolList {
+    Pools(Vec<RaydiumPool>),
+    Published {
+        #[serde(default)]
+        official: Vec<RaydiumPool>,
+        #[serde(default, rename = "unOfficial")]
+        unofficial: Vec<RaydiumPool>,
+    },
+}
+
+/// a set of raydium pools to look up by amm id or mints
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RaydiumPoolRegistry {
+    pub pools: Vec<RaydiumPool>,
+}
+
+impl RaydiumPoolRegistry {
+    /// parses either raydium's published liquidity json or a list of pools
+    pub fn from_json(json: &str) -> Result<RaydiumPoolRegistry> {
+        let pools = match serde_json::from_str(json)? {
+            PoolList::Pools(pools) => pools,
+            PoolList::Published {
+                mut official,
+                unofficial,
+            } => {
+                official.extend(unofficial);
+                official
+            }
+        };
+        Ok(RaydiumPoolRegistry { pools })
+    }
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<RaydiumPoolRegistry> {
+        RaydiumPoolRegistry::from_json(&std::fs::read_to_string(path)?)
+    }
+    /// serializes the pools as a list, the format of our own config files
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.pools)?)
+    }
+    /// returns the pool with the given amm id
+    pub fn get(&self, id: &Pubkey) -> Option<&RaydiumPool> {
+        self.pools.iter().find(|pool| pool.id == *id)
+    }
+    /// returns the pools trading `base_mint` against `quote_mint`
+    pub fn find<'a>(
+        &'a self,
+        base_mint: &'a Pubkey,
+        quote_mint: &'a Pubkey,
+    ) -> impl Iterator<Item = &'a RaydiumPool> {
+        self.pools
+            .iter()
+            .filter(move |pool| pool.base_mint == *base_mint && pool.quote_mint == *quote_mint)
+    }
+    /// returns the pools that have `mint` as either their base or quote
+    pub fn pools_for_mint<'a>(&'a self, mint: &'a Pubkey) -> impl Iterator<Item = &'a RaydiumPool> {
+        self.pools
+            .iter()
+            .filter(move |pool| pool.base_mint == *mint || pool.quote_mint == *mint)
+    }
+    /// returns the usd price of a pool's base token. pools quoted in usdc or
+    /// usdt are priced off their own serum market, others off the market of
+    /// a registered pool trading their base token against usdc or usdt
+    pub fn base_token_usd_price(&self, rpc: &Arc<RpcClient>, pool: &RaydiumPool) -> Result<f64> {
+        let stable_mints = [usdc_token_mint(), usdt_token_mint()];
+        if stable_mints.contains(&pool.quote_mint) {
+            return pool.market_price(rpc);
+        }
+        let usd_pool = stable_mints
+            .iter()
+            .find_map(|stable_mint| self.find(&pool.base_mint, stable_mint).next());
+        match usd_pool {
+            Some(usd_pool) => usd_pool.market_price(rpc),
+            None => Err(UtilsError::UsdPriceNotFound.into()),
+        }
+    }
+    pub fn quote_token_usd_price(&self, rpc: &Arc<RpcClient>, pool: &RaydiumPool) -> Result<f64> {
+        let base_usd_price = self.base_token_usd_price(rpc, pool)?;
+        pool.quote_token_usd_price(rpc, base_usd_price)
+    }
+    pub fn lp_token_price(&self, rpc: &Arc<RpcClient>, pool: &RaydiumPool) -> Result<f64> {
+        let base_usd_price = self.base_token_usd_price(rpc, pool)?;
+        pool.lp_token_price(rpc, base_usd_price)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::raydium::AMMs;
+    use crate::token_mints::wsol_token_mint;
+    use anchor_client::Cluster;
+
+    const SOL_USDC_POOL: &str = r#"{
+        "id": "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2",
+        "baseMint": "So11111111111111111111111111111111111111112",
+        "quoteMint": "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+        "lpMint": "8HoQnePLqPj4M7PUDzfw8e3Ymdwgc7NLGnaTUapubyvu",
+        "baseDecimals": 9,
+        "quoteDecimals": 6,
+        "lpDecimals": 9,
+        "version": 4,
+        "programId": "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8",
+        "authority": "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1",
+        "openOrders": "HRk9CMrpq7Jn9sh7mzxE8CChHG8dneX9p475QKz4Fsfc",
+        "targetOrders": "CZza3Ej4Mc58MnxWA385itCC9jCo3L1D7zc3LKy1bZMR",
+        "baseVault": "DQyrAcCrDXQ7NeoqGgDCZwBvWDcYmFCjSb9JtteuvPpz",
+        "quoteVault": "HLmqeL62xR1QoZ1HKKbXRrdN1p3phKpxRMb2VVopvBBz",
+        "withdrawQueue": "G7xeGGLevkRwB5f44QNgQtrPKBdMfkT6ZZwpS9xcC97n",
+        "lpVault": "Awpt6N7ZYPBa4vG4BQNFhFxDj4sxExAA9rpBAoBw2uok",
+        "marketVersion": 3,
+        "marketProgramId": "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin",
+        "marketId": "9wFFyRfZBsuAha4YcuxcXLKwMxJR43S7fPfQLusDBzvT",
+        "marketAuthority": "F8Vyqk3unwxkXukZFQeYyGmFfTG3CAX4v24iyrjEYBJV",
+        "marketBaseVault": "36c6YqAwyGKQG66XEp2dJc5JqjaBNv7sVghEtJv4c7u6",
+        "marketQuoteVault": "8CFo8bL8mZQK8abbFyypFMwEDd8tVJjHTTojMLgQTUSZ",
+        "marketBids": "14ivtgssEBoBjuZJtSAPKYgpUK7DmnSwuPMqJoVTSgKJ",
+        "marketAsks": "CEQdAFKdycHugujQg9k2wbmxjcpdYZyVLfV9WerTnafJ",
+        "marketEventQueue": "5KKsLVU6TcbVDK4BS6K1DGDxnh4Q9xjYJ8XaDCG5t8ht"
+    }"#;
+
+    fn published() -> String {
+        format!(
+            r#"{{"name": "Raydium Mainnet Liquidity Pools", "official": [{}], "unOfficial": []}}"#,
+            SOL_USDC_POOL
+        )
+    }
+
+    #[test]
+    pub fn test_pool_registry_from_json() {
+        let registry = RaydiumPoolRegistry::from_json(&published()).unwrap();
+        assert_eq!(registry.pools.len(), 1);
+        let pool = registry.get(&AMMs::SOLUSDC.amm_id()).unwrap();
+        assert_eq!(pool.open_orders, AMMs::SOLUSDC.open_orders());
+        assert_eq!(pool.lp_mint, AMMs::SOLUSDC.lp_token_mint());
+        #[allow(deprecated)]
+        let market_id = AMMs::SOLUSDC.serum_market();
+        assert_eq!(pool.market_id, market_id);
+        assert!(matches!(pool.layout().unwrap(), AmmLayouts::V4));
+        assert_eq!(
+            registry
+                .find(&wsol_token_mint(), &usdc_token_mint())
+                .count(),
+            1
+        );
+        assert_eq!(
+            registry
+                .find(&usdc_token_mint(), &wsol_token_mint())
+                .count(),
+            0
+        );
+        assert_eq!(registry.pools_for_mint(&usdc_token_mint()).count(), 1);
+
+        // our own config format is the plain list
+        let json = registry.to_json().unwrap();
+        assert_eq!(RaydiumPoolRegistry::from_json(&json).unwrap(), registry);
+
+        let mut pool = *pool;
+        pool.version = 5;
+        assert!(pool.layout().is_err());
+    }
+
+    #[test]
+    pub fn test_load_pool_registry_prices() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let registry = RaydiumPoolRegistry::from_json(&published()).unwrap();
+        let pool = registry.get(&AMMs::SOLUSDC.amm_id()).unwrap();
+        let lp_token_price = registry.lp_token_price(&rpc, pool).unwrap();
+        println!("SOL-USDC lp token price {}", lp_token_price);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::errors::UtilsError;
+use crate::raydium::AmmInfoLayout;
+use crate::serum::open_orders::unpack_open_orders;
+use anchor_client::{
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{program_pack::Pack, pubkey::Pubkey},
+};
+use anyhow::Result;
+use spl_token::amount_to_ui_amount;
+use spl_token::state::Account as TokenAccount;
+use spl_token::state::Mint as MintAccount;
+use std::sync::Arc;
+
+/// the balances an amm really holds, in native amounts. part of them sits in
+/// the amm's serum open orders, and part of the vaults is pnl the amm still
+/// owes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolReserves {
+    pub amm_id: Pubkey,
+    /// coin_vault_amount plus open_orders_coin_total, less need_take_pnl_coin
+    pub coin_amount: u64,
+    /// pc_vault_amount plus open_orders_pc_total, less need_take_pnl_pc
+    pub pc_amount: u64,
+    pub lp_supply: u64,
+    pub lp_decimals: u8,
+    pub coin_vault_amount: u64,
+    pub pc_vault_amount: u64,
+    pub open_orders_coin_total: u64,
+    pub open_orders_pc_total: u64,
+    pub need_take_pnl_coin: u64,
+    pub need_take_pnl_pc: u64,
+    /// slot the open orders, vaults and lp mint were read at
+    pub slot: u64,
+}
+
+impl PoolReserves {
+    /// loads the amm, whichever layout it has, and then its reserves. the
+    /// amm is read again along with its vaults so its pnl matches their slot
+    pub fn load(rpc: &Arc<RpcClient>, amm_key: Pubkey) -> Result<PoolReserves> {
+        let layout = AmmInfoLayout::load(rpc, amm_key)?;
+        PoolReserves::fetch(rpc, amm_key, &layout, true)
+    }
+    /// loads the open orders, vaults and lp mint of an already loaded amm
+    pub fn load_with_layout(
+        rpc: &Arc<RpcClient>,
+        amm_key: Pubkey,
+        layout: &AmmInfoLayout,
+    ) -> Result<PoolReserves> {
+        PoolReserves::fetch(rpc, amm_key, layout, false)
+    }
+    /// reads every account the reserves depend on in a single request, so
+    /// they all come from the same slot
+    fn fetch(
+        rpc: &Arc<RpcClient>,
+        amm_key: Pubkey,
+        layout: &AmmInfoLayout,
+        reload_amm: bool,
+    ) -> Result<PoolReserves> {
+        let mut keys = vec![
+            layout.amm_open_orders(),
+            layout.pool_coin_token_account(),
+            layout.pool_pc_token_account(),
+            layout.lp_mint_address(),
+        ];
+        if reload_amm {
+            keys.push(amm_key);
+        }
+        let response = rpc.get_multiple_accounts_with_commitment(&keys, rpc.commitment())?;
+        let slot = response.context.slot;
+        let mut accounts = response.value;
+        if accounts.len() != keys.len() {
+            return Err(UtilsError::InsufficientAccounts.into());
+        }
+        let open_orders_account = std::mem::take(&mut accounts[0]);
+        if open_orders_account.is_none() {
+            return Err(UtilsError::OpenOrdersAccountIsNone.into());
+        }
+        let pool_coin_token_account = std::mem::take(&mut accounts[1]);
+        if pool_coin_token_account.is_none() {
+            return Err(UtilsError::TokenAccountISNone("failed to get coin token account").into());
+        }
+        let pool_pc_token_account = std::mem::take(&mut accounts[2]);
+        if pool_pc_token_account.is_none() {
+            return Err(UtilsError::TokenAccountISNone("failed to get pc token account").into());
+        }
+        let lp_mint_account = std::mem::take(&mut accounts[3]);
+        if lp_mint_account.is_none() {
+            return Err(UtilsError::TokenAccountISNone("failed to get lp token mint").into());
+        }
+        let reloaded_layout;
+        let layout = if reload_amm {
+            let amm_account = std::mem::take(&mut accounts[4]);
+            if amm_account.is_none() {
+                return Err(UtilsError::AmmAccountIsNone.into());
+            }
+            let amm_account = amm_account.unwrap();
+            reloaded_layout = AmmInfoLayout::unpack(&amm_account.owner, &amm_account.data[..])?;
+            &reloaded_layout
+        } else {
+            layout
+        };
+        let open_orders = unpack_open_orders(&open_orders_account.unwrap().data[..])?;
+        let lp_mint = MintAccount::unpack_unchecked(&lp_mint_account.unwrap().data[..])?;
+        let pool_coin_account =
+            TokenAccount::unpack_unchecked(&pool_coin_token_account.unwrap().data[..])?;
+        let pool_pc_account =
+            TokenAccount::unpack_unchecked(&pool_pc_token_account.unwrap().data[..])?;
+        let open_orders_coin_total = open_orders.native_coin_total;
+        let open_orders_pc_total = open_orders.native_pc_total;
+        let need_take_pnl_coin = layout.need_take_pnl_coin();
+        let need_take_pnl_pc = layout.need_take_pnl_pc();
+        let coin_amount = pool_coin_account
+            .amount
+            .checked_add(open_orders_coin_total)
+            .ok_or(UtilsError::AmountOverflow("coin reserve"))?;
+        let pc_amount = pool_pc_account
+            .amount
+            .checked_add(open_orders_pc_total)
+            .ok_or(UtilsError::AmountOverflow("pc reserve"))?;
+        Ok(PoolReserves {
+            amm_id: amm_key,
+            coin_amount: coin_amount.saturating_sub(need_take_pnl_coin),
+            pc_amount: pc_amount.saturating_sub(need_take_pnl_pc),
+            lp_supply: lp_mint.supply,
+            lp_decimals: lp_mint.decimals,
+            coin_vault_amount: pool_coin_account.amount,
+            pc_vault_amount: pool_pc_account.amount,
+            open_orders_coin_total,
+            open_orders_pc_total,
+            need_take_pnl_coin,
+            need_take_pnl_pc,
+            slot,
+        })
+    }
+    /// usd value of both reserves
+    pub fn tvl_usd(
+        &self,
+        coin_decimals: u8,
+        pc_decimals: u8,
+        coin_usd_price: f64,
+        pc_usd_price: f64,
+    ) -> f64 {
+        amount_to_ui_amount(self.coin_amount, coin_decimals) * coin_usd_price
+            + amount_to_ui_amount(self.pc_amount, pc_decimals) * pc_usd_price
+    }
+    /// usd value of the reserves backing a single lp token
+    pub fn lp_token_price(
+        &self,
+        coin_decimals: u8,
+        pc_decimals: u8,
+        coin_usd_price: f64,
+        pc_usd_price: f64,
+    ) -> f64 {
+        self.tvl_usd(coin_decimals, pc_decimals, coin_usd_price, pc_usd_price)
+            / amount_to_ui_amount(self.lp_supply, self.lp_decimals)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::raydium::AMMs;
+    use anchor_client::Cluster;
+
+    #[test]
+    pub fn test_reserves_value() {
+        let reserves = PoolReserves {
+            coin_amount: 1_000_000_000_000,
+            pc_amount: 50_000_000_000,
+            lp_supply: 5_000_000_000,
+            lp_decimals: 9,
+            ..PoolReserves::default()
+        };
+        // 1000 coin at 50 usd and 50_000 pc at 1 usd backing 5 lp tokens
+        assert!((reserves.tvl_usd(9, 6, 50_f64, 1_f64) - 100_000_f64).abs() < 1e-6);
+        assert!((reserves.lp_token_price(9, 6, 50_f64, 1_f64) - 20_000_f64).abs() < 1e-6);
+    }
+
+    #[test]
+    pub fn test_load_pool_reserves() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let reserves = PoolReserves::load(&rpc, AMMs::SOLUSDC.amm_id()).unwrap();
+        assert_eq!(
+            reserves.coin_amount,
+            reserves.coin_vault_amount + reserves.open_orders_coin_total
+                - reserves.need_take_pnl_coin
+        );
+        assert!(reserves.slot > 0);
+        println!("{:#?}", reserves);
+    }
+}
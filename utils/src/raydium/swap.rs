@@ -0,0 +1,253 @@
+use crate::errors::UtilsError;
+use crate::raydium::reserves::PoolReserves;
+use crate::raydium::{AmmInfoLayout, AmmInfoLayoutV4};
+use anchor_client::{solana_client::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey};
+use anyhow::Result;
+use serum_dex::matching::Side;
+use std::sync::Arc;
+
+/// the expected outcome of a swap against a pool, in native amounts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwapQuote {
+    /// Bid swaps pc for coin, Ask swaps coin for pc
+    pub side: Side,
+    /// amount paid into the pool, including the fee
+    pub amount_in: u64,
+    /// amount received from the pool
+    pub amount_out: u64,
+    /// part of amount_in kept by the pool as the swap fee
+    pub fee: u64,
+    /// the least the swap may return once slippage is allowed for. equal to
+    /// amount_out for quotes of an exact output
+    pub min_amount_out: u64,
+    /// the most the swap may take once slippage is allowed for. equal to
+    /// amount_in for quotes of an exact input
+    pub max_amount_in: u64,
+    /// how much worse the price paid, before fees, is than the pool's spot
+    /// price, as a fraction of the spot price
+    pub price_impact: f64,
+}
+
+/// a v4 pool along with the reserves its swaps are priced against
+#[derive(Debug, Clone)]
+pub struct SwapPool {
+    pub layout: AmmInfoLayoutV4,
+    pub reserves: PoolReserves,
+}
+
+impl SwapPool {
+    /// loads the amm account and its reserves
+    pub fn load(rpc: &Arc<RpcClient>, amm_key: Pubkey) -> Result<SwapPool> {
+        let layout = AmmInfoLayoutV4::load(rpc, amm_key)?;
+        let reserves =
+            PoolReserves::load_with_layout(rpc, amm_key, &AmmInfoLayout::V4(layout.clone()))?;
+        Ok(SwapPool { layout, reserves })
+    }
+    /// quotes swapping exactly `amount_in`, the way the amm's swap_base_in
+    /// computes it. the fee is taken from the input before the constant
+    /// product is applied
+    pub fn quote_swap(&self, amount_in: u64, side: Side, slippage_bps: u64) -> Result<SwapQuote> {
+        check_slippage(slippage_bps)?;
+        let (reserve_in, reserve_out) = self.reserves(side)?;
+        let fee = ceil_div(
+            amount_in as u128 * self.layout.swap_fee_numerator as u128,
+            self.layout.swap_fee_denominator as u128,
+        );
+        let amount_in_after_fee = amount_in as u128 - fee;
+        let amount_out = reserve_out * amount_in_after_fee / (reserve_in + amount_in_after_fee);
+        let min_amount_out = amount_out * (10_000 - slippage_bps as u128) / 10_000;
+        Ok(SwapQuote {
+            side,
+            amount_in,
+            amount_out: amount_out as u64,
+            fee: fee as u64,
+            min_amount_out: min_amount_out as u64,
+            max_amount_in: amount_in,
+            price_impact: price_impact(reserve_in, reserve_out, amount_in_after_fee, amount_out),
+        })
+    }
+    /// quotes receiving exactly `amount_out`, the way the amm's swap_base_out
+    /// computes it
+    pub fn quote_swap_out(
+        &self,
+        amount_out: u64,
+        side: Side,
+        slippage_bps: u64,
+    ) -> Result<SwapQuote> {
+        check_slippage(slippage_bps)?;
+        let (reserve_in, reserve_out) = self.reserves(side)?;
+        if amount_out as u128 >= reserve_out {
+            return Err(UtilsError::InsufficientLiquidity.into());
+        }
+        let amount_in_before_fee = to_u64(
+            ceil_div(
+                reserve_in * amount_out as u128,
+                reserve_out - amount_out as u128,
+            ),
+            "swap amount",
+        )?;
+        let fee_denominator = self.layout.swap_fee_denominator as u128;
+        let amount_in = to_u64(
+            ceil_div(
+                amount_in_before_fee as u128 * fee_denominator,
+                fee_denominator - self.layout.swap_fee_numerator as u128,
+            ),
+            "swap amount",
+        )?;
+        let max_amount_in = ceil_div(amount_in as u128 * (10_000 + slippage_bps as u128), 10_000);
+        Ok(SwapQuote {
+            side,
+            amount_in,
+            amount_out,
+            fee: amount_in - amount_in_before_fee,
+            min_amount_out: amount_out,
+            max_amount_in: to_u64(max_amount_in, "swap amount")?,
+            price_impact: price_impact(
+                reserve_in,
+                reserve_out,
+                amount_in_before_fee as u128,
+                amount_out as u128,
+            ),
+        })
+    }
+    /// returns the reserves of the token going in and the token coming out
+    fn reserves(&self, side: Side) -> Result<(u128, u128)> {
+        if self.layout.swap_fee_numerator >= self.layout.swap_fee_denominator {
+            return Err(UtilsError::InvalidSwapFee.into());
+        }
+        let (coin_amount, pc_amount) = (self.reserves.coin_amount, self.reserves.pc_amount);
+        if coin_amount == 0 || pc_amount == 0 {
+            return Err(UtilsError::InsufficientLiquidity.into());
+        }
+        match side {
+            Side::Bid => Ok((pc_amount as u128, coin_amount as u128)),
+            Side::Ask => Ok((coin_amount as u128, pc_amount as u128)),
+        }
+    }
+}
+
+fn price_impact(reserve_in: u128, reserve_out: u128, amount_in: u128, amount_out: u128) -> f64 {
+    if amount_in == 0 {
+        return 0_f64;
+    }
+    let spot_price = reserve_out as f64 / reserve_in as f64;
+    let price = amount_out as f64 / amount_in as f64;
+    (spot_price - price) / spot_price
+}
+
+/// divides rounding up, the way the amm's checked_ceil_div does. a quotient
+/// below one is rounded half up instead, so small amounts may round to zero
+pub(super) fn ceil_div(num: u128, den: u128) -> u128 {
+    let quotient = num / den;
+    if quotient == 0 {
+        return if num >= den - num { 1 } else { 0 };
+    }
+    if num % den > 0 {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+/// slippage is given in basis points and can be at most 100%
+pub(super) fn check_slippage(slippage_bps: u64) -> Result<()> {
+    if slippage_bps > 10_000 {
+        return Err(UtilsError::InvalidSlippage(slippage_bps).into());
+    }
+    Ok(())
+}
+
+pub(super) fn to_u64(amount: u128, name: &'static str) -> Result<u64> {
+    if amount > u64::MAX as u128 {
+        return Err(UtilsError::AmountOverflow(name).into());
+    }
+    Ok(amount as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::raydium::AMMs;
+    use anchor_client::Cluster;
+
+    fn pool(coin_amount: u64, pc_amount: u64) -> SwapPool {
+        let mut layout = AmmInfoLayoutV4::default();
+        layout.swap_fee_numerator = 25;
+        layout.swap_fee_denominator = 10_000;
+        SwapPool {
+            layout,
+            reserves: PoolReserves {
+                coin_amount,
+                pc_amount,
+                ..PoolReserves::default()
+            },
+        }
+    }
+
+    #[test]
+    pub fn test_quote_swap() {
+        // 1000 coin against 50_000 pc
+        let pool = pool(1_000_000_000, 50_000_000_000);
+        let quote = pool.quote_swap(10_000_000, Side::Ask, 50).unwrap();
+        assert_eq!(quote.fee, 25_000);
+        // 50_000 * 9.975 / 1009.975, rounded down
+        assert_eq!(quote.amount_out, 493_824_104);
+        assert_eq!(quote.min_amount_out, 491_354_983);
+        assert_eq!(quote.max_amount_in, 10_000_000);
+        assert!((quote.price_impact - 0.009_876).abs() < 1e-5);
+
+        let quote = pool.quote_swap(500_000_000, Side::Bid, 0).unwrap();
+        assert_eq!(quote.fee, 1_250_000);
+        assert_eq!(quote.amount_out, 9_876_482);
+        assert_eq!(quote.min_amount_out, quote.amount_out);
+    }
+
+    #[test]
+    pub fn test_quote_swap_out() {
+        let pool = pool(1_000_000_000, 50_000_000_000);
+        let quote = pool.quote_swap_out(493_824_104, Side::Ask, 100).unwrap();
+        // the reverse quote needs at most what the forward quote paid
+        assert!(quote.amount_in <= 10_000_000);
+        assert!(quote.amount_in > 9_999_000);
+        let forward = pool.quote_swap(quote.amount_in, Side::Ask, 0).unwrap();
+        assert!(forward.amount_out >= 493_824_104);
+        assert_eq!(
+            quote.max_amount_in,
+            (quote.amount_in as u128 * 10_100 + 9_999) as u64 / 10_000
+        );
+        assert!(pool.quote_swap_out(50_000_000_000, Side::Ask, 0).is_err());
+        assert!(self::pool(0, 50_000_000_000)
+            .quote_swap(1, Side::Bid, 0)
+            .is_err());
+        assert!(pool.quote_swap(1_000, Side::Ask, 10_001).is_err());
+        assert!(pool.quote_swap_out(1_000, Side::Ask, 10_001).is_err());
+    }
+
+    #[test]
+    pub fn test_quote_swap_small_amounts() {
+        let pool = pool(1_000_000_000, 50_000_000_000);
+        // a fee below half a unit rounds down to zero, from half a unit up
+        // it rounds to one
+        assert_eq!(pool.quote_swap(1, Side::Ask, 0).unwrap().fee, 0);
+        assert_eq!(pool.quote_swap(199, Side::Ask, 0).unwrap().fee, 0);
+        assert_eq!(pool.quote_swap(200, Side::Ask, 0).unwrap().fee, 1);
+        assert_eq!(pool.quote_swap(401, Side::Ask, 0).unwrap().fee, 2);
+        assert_eq!(pool.quote_swap(0, Side::Ask, 0).unwrap().amount_out, 0);
+        assert_eq!(ceil_div(0, 3), 0);
+        assert_eq!(ceil_div(3, 3), 1);
+        assert_eq!(ceil_div(4, 3), 2);
+    }
+
+    #[test]
+    pub fn test_load_swap_pool() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let pool = SwapPool::load(&rpc, AMMs::SOLUSDC.amm_id()).unwrap();
+        // sell 1 sol
+        let quote = pool.quote_swap(1_000_000_000, Side::Ask, 50).unwrap();
+        println!("{:#?}", quote);
+    }
+}
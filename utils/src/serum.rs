@@ -3,41 +3,84 @@ use crate::errors::UtilsError;
 use crate::hashmap::PUBKEY_MAP;
 use anchor_client::{
     solana_client::rpc_client::RpcClient,
-    solana_sdk::{account_info::IntoAccountInfo, pubkey::Pubkey, sysvar},
+    solana_sdk::{account_info::IntoAccountInfo, pubkey::Pubkey, sysvar, sysvar::rent::Rent},
 };
 use anyhow::Result;
 use num_traits::pow::Pow;
 use serum_dex::critbit::SlabView;
 use serum_dex::matching::OrderBookState;
-use serum_dex::state::{MarketState, OpenOrders, gen_vault_signer_key};
+use serum_dex::state::{MarketState, OpenOrders};
+use summary::MarketSummary;
 use std::sync::Arc;
 use std::convert::identity;
 use anchor_lang::__private::bytemuck::cast_slice;
 
+pub mod book_diff;
+pub mod catalog;
+pub mod crank;
+pub mod event_queue;
+pub mod fees;
+pub mod instructions;
+pub mod listing;
+pub mod open_orders;
+pub mod order_book;
+pub mod quoting;
+pub mod request_queue;
+pub mod simulator;
+pub mod slippage;
+pub mod summary;
+pub mod units;
+
+/// serum prefixes every dex account with 5 bytes of "serum" padding
+pub(crate) const ACCOUNT_HEAD_PADDING: &[u8; 5] = b"serum";
+/// and suffixes it with 7 bytes of "padding" padding
+pub(crate) const ACCOUNT_TAIL_PADDING: &[u8; 7] = b"padding";
+
+/// returns the data of a dex account without its head and tail padding,
+/// or None if the padding is missing
+pub(crate) fn strip_account_padding(data: &[u8]) -> Option<&[u8]> {
+    let head = ACCOUNT_HEAD_PADDING.len();
+    let tail = ACCOUNT_TAIL_PADDING.len();
+    if data.len() < head + tail
+        || &data[..head] != ACCOUNT_HEAD_PADDING
+        || &data[data.len() - tail..] != ACCOUNT_TAIL_PADDING
+    {
+        return None;
+    }
+    Some(&data[head..data.len() - tail])
+}
+
+#[deprecated(note = "look markets up with serum::catalog::find_market")]
 pub fn ray_sol_market() -> Pubkey {
     *PUBKEY_MAP.get("ray_sol_market").unwrap()
 }
 
+#[deprecated(note = "look markets up with serum::catalog::find_market")]
 pub fn ray_srm_market() -> Pubkey {
     *PUBKEY_MAP.get("ray_srm_market").unwrap()
 }
 
+#[deprecated(note = "look markets up with serum::catalog::find_market")]
 pub fn ray_usdc_market() -> Pubkey {
     *PUBKEY_MAP.get("ray_usdc_market").unwrap()
 }
 
+#[deprecated(note = "look markets up with serum::catalog::find_market")]
 pub fn ray_usdt_market() -> Pubkey {
     *PUBKEY_MAP.get("ray_usdt_market").unwrap()
 }
 
+#[deprecated(note = "look markets up with serum::catalog::find_market")]
 pub fn sol_usdc_market() -> Pubkey {
     *PUBKEY_MAP.get("sol_usdc_market").unwrap()
 }
 
+#[deprecated(note = "look markets up with serum::catalog::find_market")]
 pub fn srm_usdc_market() -> Pubkey {
     *PUBKEY_MAP.get("srm_usdc_market").unwrap()
 }
 
+#[deprecated(note = "look markets up with serum::catalog::find_market")]
 pub fn usdt_usdc_market() -> Pubkey {
     *PUBKEY_MAP.get("usdt_usdc_market").unwrap()
 }
@@ -126,15 +169,65 @@ pub fn load_open_orders(
     let mut account_tuple = (open_orders_key, open_orders_account);
     let open_orders = account_tuple.into_account_info();
 
+    let rent_sysvar = load_rent(rpc)?;
+
+    let open_orders_state =
+        market_state.load_orders_mut(&open_orders, None, &serum_program_id, Some(rent_sysvar))?;
+    Ok(open_orders_state.to_owned())
+}
+
+/// loads the rent sysvar
+pub fn load_rent(rpc: &Arc<RpcClient>) -> Result<Rent> {
     let rent_key = sysvar::rent::id();
     let rent_account = rpc.get_account(&rent_key)?;
     let mut rent_tuple = (rent_key, rent_account);
     let rent_sysvar_account = rent_tuple.into_account_info();
     let rent_sysvar = sysvar::Sysvar::from_account_info(&rent_sysvar_account)?;
+    Ok(rent_sysvar)
+}
 
-    let open_orders_state =
-        market_state.load_orders_mut(&open_orders, None, &serum_program_id, Some(rent_sysvar))?;
-    Ok(open_orders_state.to_owned())
+/// loads the asks and bids slabs of a market and hands the resulting
+/// OrderBookState to `f`. the slabs borrow from the fetched accounts, so
+/// anything derived from them must be returned by value out of `f`
+pub fn with_order_book<T, F>(
+    rpc: &Arc<RpcClient>,
+    mut market_state: MarketState,
+    f: F,
+) -> Result<T>
+where
+    F: FnOnce(&OrderBookState) -> Result<T>,
+{
+    let asks_key = Pubkey::new(cast_slice(&identity(market_state.asks) as &[_]));
+    let bids_key = Pubkey::new(cast_slice(&identity(market_state.bids) as &[_]));
+    let mut accounts = rpc.get_multiple_accounts(&[asks_key, bids_key])?;
+    if accounts.len() != 2 {
+        return Err(UtilsError::InsufficientAccounts.into());
+    }
+    let asks_acct = std::mem::take(&mut accounts[0]);
+    if asks_acct.is_none() {
+        return Err(UtilsError::AsksAccountIsNone.into());
+    }
+    let asks_acct = asks_acct.unwrap();
+    let bids_acct = std::mem::take(&mut accounts[1]);
+    if bids_acct.is_none() {
+        return Err(UtilsError::BidsAccountIsNone.into());
+    }
+    let bids_acct = bids_acct.unwrap();
+
+    let mut asks_tuple = (asks_key, asks_acct);
+    let asks_account = asks_tuple.into_account_info();
+
+    let mut bids_tuple = (bids_key, bids_acct);
+    let bids_account = bids_tuple.into_account_info();
+
+    let mut asks = market_state.load_asks_mut(&asks_account)?;
+    let mut bids = market_state.load_bids_mut(&bids_account)?;
+    let order_book_state = OrderBookState {
+        market_state: &mut market_state,
+        asks: &mut asks,
+        bids: &mut bids,
+    };
+    f(&order_book_state)
 }
 
 /// returns the bbo prices for ask and bid
@@ -202,70 +295,15 @@ pub fn tick_size(
 }
 
 
+/// prints a summary of a market owned by `serum_program_id` to stdout
 pub fn print_serum_market_state(
     market: &MarketState,
     market_key: Pubkey,
+    serum_program_id: Pubkey,
 ) -> Result<()> {
-    let asks_key = Pubkey::new(cast_slice(&identity(market.asks) as &[_]));
-    let bids_key = Pubkey::new(cast_slice(&identity(market.bids) as &[_]));
-    let coin_mint = Pubkey::new(cast_slice(&identity(market.coin_mint) as &[_]));
-    let pc_mint = Pubkey::new(cast_slice(&identity(market.pc_mint) as &[_]));
-    let coin_vault = Pubkey::new(cast_slice(&identity(market.coin_vault) as &[_]));
-    let coin_deposits_total = market.coin_deposits_total;
-    let coin_fees_accrued = market.coin_fees_accrued;
-    let pc_vault = Pubkey::new(cast_slice(&identity(market.pc_vault) as &[_]));
-    let pc_deposits_total = market.pc_deposits_total;
-    let pc_fees_accrued = market.pc_fees_accrued;
-    let pc_dust_threshold = market.pc_dust_threshold;
-
-    let request_queue = Pubkey::new(cast_slice(&identity(market.req_q) as &[_]));
-    let event_queue = Pubkey::new(cast_slice(&identity(market.event_q) as &[_]));
-    let coin_lot_size = market.coin_lot_size;
-    let pc_lot_size = market.pc_lot_size;
-    let fee_rate_bps = market.fee_rate_bps;
-    let referrer_rebates_accrued = market.referrer_rebates_accrued;
-
-    let signer_key = gen_vault_signer_key(market.vault_signer_nonce, &market_key, &mainnet_serum_program_id())?;
-
     println!(
-        "
-            asks_key {},
-            bids_key {},
-            coin_mint {},
-            pc_mint {},
-            coin_vault {},
-            coin_deposits_total {},
-            coin_fees_accrued {},
-            pc_vault {},
-            pc_deposits_total {},
-            pc_fees_accrued {},
-            pc_dust_threshold {},
-            request_queue {},
-            event_queue {},
-            coin_lot_size {},
-            pc_lot_size {},
-            fee_rate_bps {},
-            referrer_rebates_accrued {},
-            vault_signer {},
-        ",
-        asks_key,
-        bids_key,
-        coin_mint,
-        pc_mint,
-        coin_vault,
-        coin_deposits_total,
-        coin_fees_accrued,
-        pc_vault,
-        pc_deposits_total,
-        pc_fees_accrued,
-        pc_dust_threshold,
-        request_queue,
-        event_queue,
-        coin_lot_size,
-        pc_lot_size,
-        fee_rate_bps,
-        referrer_rebates_accrued,  
-        signer_key,
+        "{}",
+        MarketSummary::new(market, market_key, serum_program_id)?
     );
     Ok(())
 }
@@ -279,6 +317,7 @@ mod test {
     use std::str::FromStr;
 
     #[test]
+    #[allow(deprecated)]
     pub fn test_market_keys() {
         assert!(ray_sol_market().to_string() == "C6tp2RVZnxBPFbnAsfTjis8BN9tycESAT4SgDQgbbrsA");
         assert!(ray_srm_market().to_string() == "Cm4MmknScg7qbKqytb1mM92xgDxv3TNXos4tKbBqTDy7");
@@ -333,5 +372,8 @@ mod test {
             open_orders.free_slot_bits
         });
         println!("open order is_bid_bits {}", { open_orders.is_bid_bits });
+        for order in open_orders::open_orders_iter(&open_orders, None) {
+            println!("open order {:#?}", order);
+        }
     }
 }
@@ -0,0 +1,299 @@
+use crate::broadcast_channel::UnboundedBroadcast;
+use crate::serum::order_book::{L2Level, L2OrderBook, L3Order, L3OrderBook};
+use serum_dex::matching::Side;
+use std::collections::{HashMap, HashSet};
+
+/// a single change between two consecutive snapshots of an order book
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BookDelta {
+    LevelAdded {
+        side: Side,
+        level: L2Level,
+    },
+    /// the quantity resting at a price changed
+    LevelChanged {
+        side: Side,
+        level: L2Level,
+        previous_lot_quantity: u64,
+    },
+    LevelRemoved {
+        side: Side,
+        lot_price: u64,
+    },
+    OrderInserted(L3Order),
+    /// the order was partially filled
+    OrderChanged {
+        order: L3Order,
+        previous_lot_quantity: u64,
+    },
+    /// the order left the book, because it was either cancelled or filled
+    OrderRemoved(L3Order),
+}
+
+/// returns the level changes that turn `previous` into `current`, asks
+/// first and each side in priority order. with a depth limited snapshot,
+/// levels moving past the depth show up as removed
+pub fn diff_l2(previous: &L2OrderBook, current: &L2OrderBook) -> Vec<BookDelta> {
+    let mut deltas = Vec::new();
+    diff_levels(Side::Ask, &previous.asks, &current.asks, &mut deltas);
+    diff_levels(Side::Bid, &previous.bids, &current.bids, &mut deltas);
+    deltas
+}
+
+/// returns the order changes that turn `previous` into `current`, asks
+/// first. removals come before insertions and quantity changes, which are
+/// listed in the current priority order
+pub fn diff_l3(previous: &L3OrderBook, current: &L3OrderBook) -> Vec<BookDelta> {
+    let mut deltas = Vec::new();
+    diff_orders(&previous.asks, &current.asks, &mut deltas);
+    diff_orders(&previous.bids, &current.bids, &mut deltas);
+    deltas
+}
+
+/// merges two price sorted sides of an L2 book
+fn diff_levels(side: Side, previous: &[L2Level], current: &[L2Level], deltas: &mut Vec<BookDelta>) {
+    let ahead = |a: u64, b: u64| match side {
+        Side::Ask => a < b,
+        Side::Bid => a > b,
+    };
+    let (mut i, mut j) = (0, 0);
+    loop {
+        match (previous.get(i), current.get(j)) {
+            (Some(old), Some(new)) if old.lot_price == new.lot_price => {
+                if old.lot_quantity != new.lot_quantity {
+                    deltas.push(BookDelta::LevelChanged {
+                        side,
+                        level: *new,
+                        previous_lot_quantity: old.lot_quantity,
+                    });
+                }
+                i += 1;
+                j += 1;
+            }
+            (Some(old), Some(new)) if ahead(old.lot_price, new.lot_price) => {
+                deltas.push(BookDelta::LevelRemoved {
+                    side,
+                    lot_price: old.lot_price,
+                });
+                i += 1;
+            }
+            (Some(old), None) => {
+                deltas.push(BookDelta::LevelRemoved {
+                    side,
+                    lot_price: old.lot_price,
+                });
+                i += 1;
+            }
+            (_, Some(new)) => {
+                deltas.push(BookDelta::LevelAdded { side, level: *new });
+                j += 1;
+            }
+            (None, None) => break,
+        }
+    }
+}
+
+fn diff_orders(previous: &[L3Order], current: &[L3Order], deltas: &mut Vec<BookDelta>) {
+    let previous_by_id: HashMap<u128, &L3Order> = previous
+        .iter()
+        .map(|order| (order.order_id, order))
+        .collect();
+    let current_ids: HashSet<u128> = current.iter().map(|order| order.order_id).collect();
+    deltas.extend(
+        previous
+            .iter()
+            .filter(|order| !current_ids.contains(&order.order_id))
+            .map(|order| BookDelta::OrderRemoved(*order)),
+    );
+    for order in current {
+        match previous_by_id.get(&order.order_id) {
+            None => deltas.push(BookDelta::OrderInserted(*order)),
+            Some(old) if old.lot_quantity != order.lot_quantity => {
+                deltas.push(BookDelta::OrderChanged {
+                    order: *order,
+                    previous_lot_quantity: old.lot_quantity,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// remembers the last snapshot of a market and broadcasts the deltas of
+/// every new snapshot to its subscribers. the first snapshot is sent as
+/// every level or order being added
+pub struct BookDeltaStream {
+    previous_l2: L2OrderBook,
+    previous_l3: L3OrderBook,
+    broadcast: UnboundedBroadcast<BookDelta>,
+}
+
+impl BookDeltaStream {
+    pub fn new() -> BookDeltaStream {
+        BookDeltaStream {
+            previous_l2: L2OrderBook::default(),
+            previous_l3: L3OrderBook::default(),
+            broadcast: UnboundedBroadcast::new(),
+        }
+    }
+    pub fn subscribe(&mut self) -> crossbeam_channel::Receiver<BookDelta> {
+        self.broadcast.subscribe()
+    }
+    /// diffs `book` against the previous L2 snapshot, sends the deltas and
+    /// returns how many there were. subscribers that hung up are dropped
+    pub fn push_l2(&mut self, book: L2OrderBook) -> usize {
+        let deltas = diff_l2(&self.previous_l2, &book);
+        self.previous_l2 = book;
+        self.send(deltas)
+    }
+    /// diffs `book` against the previous L3 snapshot, sends the deltas and
+    /// returns how many there were. subscribers that hung up are dropped
+    pub fn push_l3(&mut self, book: L3OrderBook) -> usize {
+        let deltas = diff_l3(&self.previous_l3, &book);
+        self.previous_l3 = book;
+        self.send(deltas)
+    }
+    fn send(&mut self, deltas: Vec<BookDelta>) -> usize {
+        let sent = deltas.len();
+        for delta in deltas {
+            self.broadcast.send_pruned(delta);
+        }
+        sent
+    }
+}
+
+impl Default for BookDeltaStream {
+    fn default() -> Self {
+        BookDeltaStream::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anchor_client::solana_sdk::pubkey::Pubkey;
+
+    fn level(lot_price: u64, lot_quantity: u64) -> L2Level {
+        L2Level {
+            lot_price,
+            lot_quantity,
+            price: lot_price as f64,
+            quantity: lot_quantity as f64,
+        }
+    }
+
+    fn order(side: Side, order_id: u128, lot_quantity: u64) -> L3Order {
+        L3Order {
+            side,
+            order_id,
+            lot_price: (order_id >> 64) as u64,
+            lot_quantity,
+            price: 0_f64,
+            quantity: 0_f64,
+            owner: Pubkey::default(),
+            owner_slot: 0,
+            client_order_id: 0,
+        }
+    }
+
+    #[test]
+    pub fn test_diff_l2() {
+        let previous = L2OrderBook {
+            asks: vec![level(10, 1), level(11, 2), level(13, 1)],
+            bids: vec![level(9, 4), level(7, 1)],
+        };
+        let current = L2OrderBook {
+            asks: vec![level(11, 3), level(12, 1), level(13, 1)],
+            bids: vec![level(9, 4), level(8, 2), level(7, 1)],
+        };
+        let deltas = diff_l2(&previous, &current);
+        assert_eq!(
+            deltas,
+            vec![
+                BookDelta::LevelRemoved {
+                    side: Side::Ask,
+                    lot_price: 10
+                },
+                BookDelta::LevelChanged {
+                    side: Side::Ask,
+                    level: level(11, 3),
+                    previous_lot_quantity: 2
+                },
+                BookDelta::LevelAdded {
+                    side: Side::Ask,
+                    level: level(12, 1)
+                },
+                BookDelta::LevelAdded {
+                    side: Side::Bid,
+                    level: level(8, 2)
+                },
+            ]
+        );
+        assert!(diff_l2(&current, &current).is_empty());
+    }
+
+    #[test]
+    pub fn test_diff_l3() {
+        let previous = L3OrderBook {
+            asks: vec![order(Side::Ask, 10 << 64, 5), order(Side::Ask, 11 << 64, 2)],
+            bids: vec![order(Side::Bid, 9 << 64, 1)],
+        };
+        let current = L3OrderBook {
+            asks: vec![order(Side::Ask, 11 << 64, 1)],
+            bids: vec![order(Side::Bid, 9 << 64, 1), order(Side::Bid, 8 << 64, 3)],
+        };
+        let deltas = diff_l3(&previous, &current);
+        assert_eq!(
+            deltas,
+            vec![
+                BookDelta::OrderRemoved(order(Side::Ask, 10 << 64, 5)),
+                BookDelta::OrderChanged {
+                    order: order(Side::Ask, 11 << 64, 1),
+                    previous_lot_quantity: 2
+                },
+                BookDelta::OrderInserted(order(Side::Bid, 8 << 64, 3)),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_book_delta_stream() {
+        let mut stream = BookDeltaStream::new();
+        let receiver = stream.subscribe();
+        let book = L2OrderBook {
+            asks: vec![level(10, 1)],
+            bids: vec![level(9, 1)],
+        };
+        assert_eq!(stream.push_l2(book.clone()), 2);
+        assert_eq!(stream.push_l2(book), 0);
+        assert_eq!(receiver.try_iter().count(), 2);
+    }
+
+    #[test]
+    pub fn test_book_delta_stream_dropped_subscriber() {
+        let mut stream = BookDeltaStream::new();
+        let dropped = stream.subscribe();
+        let receiver = stream.subscribe();
+        drop(dropped);
+        let book = L3OrderBook {
+            asks: vec![order(Side::Ask, 10 << 64, 5)],
+            bids: vec![],
+        };
+        assert_eq!(stream.push_l3(book), 1);
+        let filled = L3OrderBook {
+            asks: vec![order(Side::Ask, 10 << 64, 2)],
+            bids: vec![],
+        };
+        assert_eq!(stream.push_l3(filled), 1);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<BookDelta>>(),
+            vec![
+                BookDelta::OrderInserted(order(Side::Ask, 10 << 64, 5)),
+                BookDelta::OrderChanged {
+                    order: order(Side::Ask, 10 << 64, 2),
+                    previous_lot_quantity: 5
+                },
+            ]
+        );
+    }
+}
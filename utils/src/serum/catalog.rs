@@ -0,0 +1,317 @@
+use crate::errors::UtilsError;
+use crate::serum::instructions::MarketAccounts;
+use crate::serum::listing::market_account_len;
+use anchor_client::{
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+    },
+    solana_sdk::{account_info::IntoAccountInfo, pubkey::Pubkey},
+};
+use anchor_lang::__private::bytemuck::cast_slice;
+use anyhow::Result;
+use serum_dex::state::MarketState;
+use solana_account_decoder::UiAccountEncoding;
+use std::convert::identity;
+use std::sync::Arc;
+
+/// offset of the coin mint within a market account, after the head padding,
+/// account flags, own address and vault signer nonce
+pub const MARKET_COIN_MINT_OFFSET: usize = 53;
+/// offset of the pc mint within a market account
+pub const MARKET_PC_MINT_OFFSET: usize = 85;
+
+/// the accounts and parameters of a discovered market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketInfo {
+    pub address: Pubkey,
+    pub program_id: Pubkey,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub request_queue: Pubkey,
+    pub event_queue: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub vault_signer_nonce: u64,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub pc_dust_threshold: u64,
+    pub fee_rate_bps: u64,
+    pub coin_deposits_total: u64,
+    pub pc_deposits_total: u64,
+}
+
+impl MarketInfo {
+    pub fn new(address: Pubkey, program_id: Pubkey, market_state: &MarketState) -> MarketInfo {
+        MarketInfo {
+            address,
+            program_id,
+            coin_mint: Pubkey::new(cast_slice(&identity(market_state.coin_mint) as &[_])),
+            pc_mint: Pubkey::new(cast_slice(&identity(market_state.pc_mint) as &[_])),
+            coin_vault: Pubkey::new(cast_slice(&identity(market_state.coin_vault) as &[_])),
+            pc_vault: Pubkey::new(cast_slice(&identity(market_state.pc_vault) as &[_])),
+            request_queue: Pubkey::new(cast_slice(&identity(market_state.req_q) as &[_])),
+            event_queue: Pubkey::new(cast_slice(&identity(market_state.event_q) as &[_])),
+            bids: Pubkey::new(cast_slice(&identity(market_state.bids) as &[_])),
+            asks: Pubkey::new(cast_slice(&identity(market_state.asks) as &[_])),
+            vault_signer_nonce: market_state.vault_signer_nonce,
+            coin_lot_size: market_state.coin_lot_size,
+            pc_lot_size: market_state.pc_lot_size,
+            pc_dust_threshold: market_state.pc_dust_threshold,
+            fee_rate_bps: market_state.fee_rate_bps,
+            coin_deposits_total: market_state.coin_deposits_total,
+            pc_deposits_total: market_state.pc_deposits_total,
+        }
+    }
+    /// returns the accounts instructions for this market are built from
+    pub fn market_accounts(
+        &self,
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+    ) -> Result<MarketAccounts> {
+        let vault_signer = serum_dex::state::gen_vault_signer_key(
+            self.vault_signer_nonce,
+            &self.address,
+            &self.program_id,
+        )?;
+        Ok(MarketAccounts {
+            program_id: self.program_id,
+            market: self.address,
+            request_queue: self.request_queue,
+            event_queue: self.event_queue,
+            bids: self.bids,
+            asks: self.asks,
+            coin_mint: self.coin_mint,
+            pc_mint: self.pc_mint,
+            coin_vault: self.coin_vault,
+            pc_vault: self.pc_vault,
+            vault_signer,
+            coin_lot_size: self.coin_lot_size,
+            pc_lot_size: self.pc_lot_size,
+            base_token_decimals,
+            quote_token_decimals,
+        })
+    }
+}
+
+/// scans the market accounts of a dex program, optionally only those
+/// trading `coin_mint` and/or `pc_mint`. accounts that fail to load as a
+/// market, such as disabled markets, are skipped. only accounts of the
+/// MarketState size are scanned, so permissioned markets, whose larger
+/// MarketStateV2 accounts this dex version does not load, are not found
+pub fn discover_markets(
+    rpc: &Arc<RpcClient>,
+    program_id: Pubkey,
+    coin_mint: Option<Pubkey>,
+    pc_mint: Option<Pubkey>,
+) -> Result<Vec<MarketInfo>> {
+    let mut filters = vec![RpcFilterType::DataSize(market_account_len() as u64)];
+    if let Some(coin_mint) = coin_mint {
+        filters.push(RpcFilterType::Memcmp(Memcmp {
+            offset: MARKET_COIN_MINT_OFFSET,
+            bytes: MemcmpEncodedBytes::Binary(coin_mint.to_string()),
+            encoding: None,
+        }));
+    }
+    if let Some(pc_mint) = pc_mint {
+        filters.push(RpcFilterType::Memcmp(Memcmp {
+            offset: MARKET_PC_MINT_OFFSET,
+            bytes: MemcmpEncodedBytes::Binary(pc_mint.to_string()),
+            encoding: None,
+        }));
+    }
+    let config = RpcProgramAccountsConfig {
+        filters: Some(filters),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let accounts = rpc.get_program_accounts_with_config(&program_id, config)?;
+    let mut markets = Vec::with_capacity(accounts.len());
+    for (address, account) in accounts.into_iter() {
+        let mut account_tuple = (address, account);
+        let market_account = account_tuple.into_account_info();
+        let market_state = MarketState::load(&market_account, &program_id);
+        if let Ok(market_state) = market_state {
+            markets.push(MarketInfo::new(address, program_id, &market_state));
+        }
+    }
+    Ok(markets)
+}
+
+/// the markets of a dex program, discovered on chain
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarketCatalog {
+    pub markets: Vec<MarketInfo>,
+}
+
+impl MarketCatalog {
+    /// discovers every market of the given dex program
+    pub fn load(rpc: &Arc<RpcClient>, program_id: Pubkey) -> Result<MarketCatalog> {
+        Ok(MarketCatalog {
+            markets: discover_markets(rpc, program_id, None, None)?,
+        })
+    }
+    /// discovers the markets of the given dex program trading `coin_mint`
+    /// against `pc_mint`
+    pub fn load_pair(
+        rpc: &Arc<RpcClient>,
+        program_id: Pubkey,
+        coin_mint: Pubkey,
+        pc_mint: Pubkey,
+    ) -> Result<MarketCatalog> {
+        Ok(MarketCatalog {
+            markets: discover_markets(rpc, program_id, Some(coin_mint), Some(pc_mint))?,
+        })
+    }
+    /// returns the market with the given address
+    pub fn get(&self, address: &Pubkey) -> Option<&MarketInfo> {
+        self.markets
+            .iter()
+            .find(|market| market.address == *address)
+    }
+    /// returns the markets trading `coin_mint` against `pc_mint`. there can be
+    /// several, as anyone can list a market
+    pub fn find<'a>(
+        &'a self,
+        coin_mint: &'a Pubkey,
+        pc_mint: &'a Pubkey,
+    ) -> impl Iterator<Item = &'a MarketInfo> {
+        self.markets
+            .iter()
+            .filter(move |market| market.coin_mint == *coin_mint && market.pc_mint == *pc_mint)
+    }
+    /// returns the market trading `coin_mint` against `pc_mint` that holds
+    /// the most pc deposits, which is the market a pair is known by when
+    /// copies of it were listed
+    pub fn primary(&self, coin_mint: &Pubkey, pc_mint: &Pubkey) -> Option<&MarketInfo> {
+        self.markets
+            .iter()
+            .filter(|market| market.coin_mint == *coin_mint && market.pc_mint == *pc_mint)
+            .max_by_key(|market| market.pc_deposits_total)
+    }
+    /// returns the markets that have `mint` as either their coin or pc
+    pub fn markets_for_mint<'a>(
+        &'a self,
+        mint: &'a Pubkey,
+    ) -> impl Iterator<Item = &'a MarketInfo> {
+        self.markets
+            .iter()
+            .filter(move |market| market.coin_mint == *mint || market.pc_mint == *mint)
+    }
+}
+
+/// discovers the primary market of a dex program trading `coin_mint`
+/// against `pc_mint`
+pub fn find_market(
+    rpc: &Arc<RpcClient>,
+    program_id: Pubkey,
+    coin_mint: Pubkey,
+    pc_mint: Pubkey,
+) -> Result<MarketInfo> {
+    MarketCatalog::load_pair(rpc, program_id, coin_mint, pc_mint)?
+        .primary(&coin_mint, &pc_mint)
+        .copied()
+        .ok_or_else(|| UtilsError::MarketNotFound.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serum::mainnet_serum_program_id;
+    use crate::token_mints::{usdc_token_mint, wsol_token_mint};
+    use anchor_client::Cluster;
+
+    fn market(address: u8, coin_mint: Pubkey, pc_mint: Pubkey) -> MarketInfo {
+        MarketInfo {
+            address: Pubkey::new_from_array([address; 32]),
+            program_id: Pubkey::default(),
+            coin_mint,
+            pc_mint,
+            coin_vault: Pubkey::default(),
+            pc_vault: Pubkey::default(),
+            request_queue: Pubkey::default(),
+            event_queue: Pubkey::default(),
+            bids: Pubkey::default(),
+            asks: Pubkey::default(),
+            vault_signer_nonce: 0,
+            coin_lot_size: 1,
+            pc_lot_size: 1,
+            pc_dust_threshold: 0,
+            fee_rate_bps: 0,
+            coin_deposits_total: 0,
+            pc_deposits_total: address as u64,
+        }
+    }
+
+    #[test]
+    pub fn test_market_catalog_lookups() {
+        let (sol, usdc, ray) = (
+            Pubkey::new_from_array([10; 32]),
+            Pubkey::new_from_array([11; 32]),
+            Pubkey::new_from_array([12; 32]),
+        );
+        let catalog = MarketCatalog {
+            markets: vec![
+                market(1, sol, usdc),
+                market(2, ray, usdc),
+                market(3, ray, sol),
+                market(4, sol, usdc),
+            ],
+        };
+        assert_eq!(catalog.find(&sol, &usdc).count(), 2);
+        assert_eq!(catalog.find(&usdc, &sol).count(), 0);
+        assert_eq!(
+            catalog.primary(&sol, &usdc).unwrap().address,
+            Pubkey::new_from_array([4; 32])
+        );
+        assert!(catalog.primary(&usdc, &ray).is_none());
+        assert_eq!(catalog.markets_for_mint(&ray).count(), 2);
+        assert_eq!(
+            catalog
+                .get(&Pubkey::new_from_array([3; 32]))
+                .unwrap()
+                .pc_mint,
+            sol
+        );
+        assert!(catalog.get(&Pubkey::new_from_array([5; 32])).is_none());
+    }
+
+    #[test]
+    pub fn test_load_discover_markets() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let markets = discover_markets(
+            &rpc,
+            mainnet_serum_program_id(),
+            Some(wsol_token_mint()),
+            Some(usdc_token_mint()),
+        )
+        .unwrap();
+        assert!(markets
+            .iter()
+            .all(|market| market.coin_mint == wsol_token_mint()
+                && market.pc_mint == usdc_token_mint()));
+        let sol_usdc = find_market(
+            &rpc,
+            mainnet_serum_program_id(),
+            wsol_token_mint(),
+            usdc_token_mint(),
+        )
+        .unwrap();
+        assert!(markets
+            .iter()
+            .any(|market| market.address == sol_usdc.address));
+        println!("sol/usdc market {:#?}", sol_usdc);
+        assert_eq!(sol_usdc.coin_lot_size, 100_000_000);
+        assert_eq!(sol_usdc.pc_lot_size, 100);
+    }
+}
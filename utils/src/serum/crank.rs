@@ -0,0 +1,178 @@
+use crate::serum::{self, event_queue::EventQueue};
+use anchor_client::{
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{instruction::Instruction, pubkey::Pubkey},
+};
+use anchor_lang::__private::bytemuck::cast_slice;
+use anyhow::Result;
+use arrayref::array_refs;
+use serum_dex::instruction as dex_instruction;
+use serum_dex::state::MarketState;
+use std::convert::identity;
+use std::sync::Arc;
+
+/// how many open orders accounts a single consume_events instruction
+/// references by default. together with the market, event queue and fee
+/// receivable accounts this keeps the transaction well below the size limit
+pub const DEFAULT_MAX_OPEN_ORDERS_PER_CONSUME_EVENTS: usize = 20;
+
+/// builds consume_events instructions that crank a market's event queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsumeEventsCrank {
+    pub program_id: Pubkey,
+    pub market: Pubkey,
+    pub event_queue: Pubkey,
+    /// accounts the instruction requires for coin and pc fees. the dex does
+    /// not move funds into them, so any token accounts will do
+    pub coin_fee_receivable: Pubkey,
+    pub pc_fee_receivable: Pubkey,
+    pub max_open_orders_per_instruction: usize,
+}
+
+impl ConsumeEventsCrank {
+    pub fn new(
+        market_key: Pubkey,
+        market_state: &MarketState,
+        program_id: Pubkey,
+        coin_fee_receivable: Pubkey,
+        pc_fee_receivable: Pubkey,
+    ) -> ConsumeEventsCrank {
+        ConsumeEventsCrank {
+            program_id,
+            market: market_key,
+            event_queue: Pubkey::new(cast_slice(&identity(market_state.event_q) as &[_])),
+            coin_fee_receivable,
+            pc_fee_receivable,
+            max_open_orders_per_instruction: DEFAULT_MAX_OPEN_ORDERS_PER_CONSUME_EVENTS,
+        }
+    }
+    /// loads the market and returns a crank for it
+    pub fn load(
+        rpc: &Arc<RpcClient>,
+        market_key: Pubkey,
+        program_id: Pubkey,
+        coin_fee_receivable: Pubkey,
+        pc_fee_receivable: Pubkey,
+    ) -> Result<ConsumeEventsCrank> {
+        let market_state = serum::load_serum_market(rpc, market_key, program_id)?;
+        Ok(ConsumeEventsCrank::new(
+            market_key,
+            &market_state,
+            program_id,
+            coin_fee_receivable,
+            pc_fee_receivable,
+        ))
+    }
+    /// fetches the current event queue and returns the instructions that
+    /// consume every pending event
+    pub fn load_instructions(&self, rpc: &Arc<RpcClient>) -> Result<Vec<Instruction>> {
+        let event_queue_data = rpc.get_account_data(&self.event_queue)?;
+        let event_queue = EventQueue::unpack_from_slice(&event_queue_data[..])?;
+        self.instructions(&event_queue)
+    }
+    /// returns consume_events instructions covering every pending event of
+    /// `event_queue`.
+    ///
+    /// the dex consumes events in queue order and stops at the first event
+    /// whose open orders account was not passed in, so each instruction
+    /// covers a contiguous run of events and assumes the instructions before
+    /// it have already been executed. send them in order
+    pub fn instructions(&self, event_queue: &EventQueue) -> Result<Vec<Instruction>> {
+        let owners = event_queue.events.iter().map(|event| event.owner);
+        batch_open_orders(owners, self.max_open_orders_per_instruction)
+            .into_iter()
+            .map(|(open_orders, limit)| {
+                Ok(dex_instruction::consume_events(
+                    &self.program_id,
+                    open_orders.iter().collect(),
+                    &self.market,
+                    &self.event_queue,
+                    &self.coin_fee_receivable,
+                    &self.pc_fee_receivable,
+                    limit,
+                )?)
+            })
+            .collect()
+    }
+}
+
+/// splits the owners of consecutive events into batches referencing at most
+/// `max_open_orders` distinct accounts and u16::MAX events. each batch's
+/// accounts are sorted the way the dex binary searches them
+fn batch_open_orders(
+    owners: impl Iterator<Item = Pubkey>,
+    max_open_orders: usize,
+) -> Vec<(Vec<Pubkey>, u16)> {
+    let max_open_orders = max_open_orders.max(1);
+    let mut batches = Vec::new();
+    let mut open_orders: Vec<Pubkey> = Vec::new();
+    let mut events: u16 = 0;
+    for owner in owners {
+        let known = open_orders.contains(&owner);
+        if (!known && open_orders.len() == max_open_orders) || events == u16::MAX {
+            batches.push((std::mem::take(&mut open_orders), events));
+            events = 0;
+        }
+        if !open_orders.contains(&owner) {
+            open_orders.push(owner);
+        }
+        events += 1;
+    }
+    if events > 0 {
+        batches.push((open_orders, events));
+    }
+    for (open_orders, _) in batches.iter_mut() {
+        open_orders.sort_by_key(pubkey_words);
+    }
+    batches
+}
+
+/// the dex compares account keys as four little endian u64 words rather
+/// than as bytes, so they need to be sorted the same way
+pub(crate) fn pubkey_words(key: &Pubkey) -> [u64; 4] {
+    let bytes = key.to_bytes();
+    let (a, b, c, d) = array_refs![&bytes, 8, 8, 8, 8];
+    [
+        u64::from_le_bytes(*a),
+        u64::from_le_bytes(*b),
+        u64::from_le_bytes(*c),
+        u64::from_le_bytes(*d),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(first: u8, second: u8) -> Pubkey {
+        let mut bytes = [0_u8; 32];
+        bytes[0] = first;
+        bytes[1] = second;
+        Pubkey::new_from_array(bytes)
+    }
+
+    #[test]
+    pub fn test_pubkey_words_order() {
+        // byte order would put `b` first, word order puts `a` first
+        let a = key(1, 0);
+        let b = key(0, 1);
+        let mut keys = vec![a, b];
+        keys.sort_by_key(pubkey_words);
+        assert_eq!(keys, vec![a, b]);
+        let c = key(2, 0);
+        let mut keys = vec![b, c, a];
+        keys.sort_by_key(pubkey_words);
+        assert_eq!(keys, vec![a, c, b]);
+    }
+
+    #[test]
+    pub fn test_batch_open_orders() {
+        let (a, b, c) = (key(1, 0), key(2, 0), key(3, 0));
+        let owners = vec![c, a, c, b, a, b, a];
+        let batches = batch_open_orders(owners.clone().into_iter(), 2);
+        assert_eq!(batches, vec![(vec![a, c], 3), (vec![a, b], 4)]);
+        let batches = batch_open_orders(owners.into_iter(), 3);
+        assert_eq!(batches, vec![(vec![a, b, c], 7)]);
+        assert!(batch_open_orders(vec![].into_iter(), 3).is_empty());
+    }
+}
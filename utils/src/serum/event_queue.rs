@@ -0,0 +1,308 @@
+use crate::errors::UtilsError;
+use crate::serum::strip_account_padding;
+use anchor_client::{solana_client::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey};
+use anchor_lang::__private::bytemuck::cast_slice;
+use anyhow::Result;
+use arrayref::{array_ref, array_refs};
+use serum_dex::matching::Side;
+use serum_dex::state::{AccountFlag, MarketState};
+use std::convert::identity;
+use std::sync::Arc;
+
+/// size of the event queue header, after the account padding
+pub const EVENT_QUEUE_HEADER_LEN: usize = 32;
+/// size of a single event
+pub const EVENT_LEN: usize = 88;
+
+const EVENT_FLAG_FILL: u8 = 0x1;
+const EVENT_FLAG_OUT: u8 = 0x2;
+const EVENT_FLAG_BID: u8 = 0x4;
+const EVENT_FLAG_MAKER: u8 = 0x8;
+const EVENT_FLAG_RELEASE_FUNDS: u8 = 0x10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// an order was (partially) matched
+    Fill,
+    /// an order left the book, either fully filled or cancelled
+    Out,
+}
+
+/// a decoded serum event queue entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    /// sequence number of the event, increasing by one for every event the
+    /// market has ever pushed
+    pub seq_num: u64,
+    pub kind: EventKind,
+    pub side: Side,
+    /// true if the order was resting on the book, false for the taker
+    pub maker: bool,
+    /// set on out events when the owner's locked funds become free
+    pub release_funds: bool,
+    pub owner_slot: u8,
+    pub fee_tier: u8,
+    /// native amount credited to the owner, coin for bids and pc for asks
+    pub native_qty_released: u64,
+    /// native amount debited from the owner, pc for bids and coin for asks
+    pub native_qty_paid: u64,
+    /// taker fee paid, or maker rebate received, in native pc
+    pub native_fee_or_rebate: u64,
+    pub order_id: u128,
+    /// the open orders account of the order
+    pub owner: Pubkey,
+    pub client_order_id: u64,
+}
+
+impl Event {
+    pub fn is_fill(&self) -> bool {
+        self.kind == EventKind::Fill
+    }
+    /// native coin moved by the event
+    pub fn native_coin_qty(&self) -> u64 {
+        match self.side {
+            Side::Bid => self.native_qty_released,
+            Side::Ask => self.native_qty_paid,
+        }
+    }
+    /// native pc moved by the event, fees included
+    pub fn native_pc_qty(&self) -> u64 {
+        match self.side {
+            Side::Bid => self.native_qty_paid,
+            Side::Ask => self.native_qty_released,
+        }
+    }
+    fn unpack_from_slice(src: &[u8], seq_num: u64) -> Result<Event> {
+        let input = array_ref![src, 0, EVENT_LEN];
+        let (
+            event_flags,
+            owner_slot,
+            fee_tier,
+            _padding,
+            native_qty_released,
+            native_qty_paid,
+            native_fee_or_rebate,
+            order_id,
+            owner,
+            client_order_id,
+        ) = array_refs![input, 1, 1, 1, 5, 8, 8, 8, 16, 32, 8];
+        let event_flags = event_flags[0];
+        let kind = match (
+            event_flags & EVENT_FLAG_FILL != 0,
+            event_flags & EVENT_FLAG_OUT != 0,
+        ) {
+            (true, false) => EventKind::Fill,
+            (false, true) => EventKind::Out,
+            _ => return Err(UtilsError::InvalidDexAccount("event queue").into()),
+        };
+        let side = if event_flags & EVENT_FLAG_BID != 0 {
+            Side::Bid
+        } else {
+            Side::Ask
+        };
+        Ok(Event {
+            seq_num,
+            kind,
+            side,
+            maker: event_flags & EVENT_FLAG_MAKER != 0,
+            release_funds: event_flags & EVENT_FLAG_RELEASE_FUNDS != 0,
+            owner_slot: owner_slot[0],
+            fee_tier: fee_tier[0],
+            native_qty_released: u64::from_le_bytes(*native_qty_released),
+            native_qty_paid: u64::from_le_bytes(*native_qty_paid),
+            native_fee_or_rebate: u64::from_le_bytes(*native_fee_or_rebate),
+            order_id: u128::from_le_bytes(*order_id),
+            owner: Pubkey::new_from_array(*owner),
+            client_order_id: u64::from_le_bytes(*client_order_id),
+        })
+    }
+}
+
+/// the unconsumed contents of a serum event queue
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventQueue {
+    pub account_flags: u64,
+    /// ring buffer index of the oldest unconsumed event
+    pub head: u64,
+    /// number of unconsumed events
+    pub count: u64,
+    /// sequence number the next pushed event will get
+    pub seq_num: u64,
+    /// the unconsumed events, oldest first
+    pub events: Vec<Event>,
+}
+
+impl EventQueue {
+    /// loads the event queue of the given market
+    pub fn load(rpc: &Arc<RpcClient>, market_state: &MarketState) -> Result<EventQueue> {
+        let event_queue_key = Pubkey::new(cast_slice(&identity(market_state.event_q) as &[_]));
+        let account_data = rpc.get_account_data(&event_queue_key)?;
+        EventQueue::unpack_from_slice(&account_data[..])
+    }
+    /// decodes a raw event queue account, including its padding
+    pub fn unpack_from_slice(src: &[u8]) -> Result<EventQueue> {
+        let data = strip_account_padding(src);
+        if data.is_none() || data.unwrap().len() < EVENT_QUEUE_HEADER_LEN {
+            return Err(UtilsError::InvalidDexAccount("event queue").into());
+        }
+        let data = data.unwrap();
+        let (header, buf) = data.split_at(EVENT_QUEUE_HEADER_LEN);
+        let header = array_ref![header, 0, EVENT_QUEUE_HEADER_LEN];
+        let (account_flags, head, count, seq_num) = array_refs![header, 8, 8, 8, 8];
+        let account_flags = u64::from_le_bytes(*account_flags);
+        let head = u64::from_le_bytes(*head);
+        let count = u64::from_le_bytes(*count);
+        let seq_num = u64::from_le_bytes(*seq_num);
+
+        let expected_flags = AccountFlag::Initialized as u64 | AccountFlag::EventQueue as u64;
+        let capacity = (buf.len() / EVENT_LEN) as u64;
+        if account_flags & expected_flags != expected_flags
+            || count > capacity
+            || (capacity > 0 && head >= capacity)
+            || count > seq_num
+        {
+            return Err(UtilsError::InvalidDexAccount("event queue").into());
+        }
+        let first_seq_num = seq_num - count;
+        let mut events = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let offset = (((head + i) % capacity) as usize) * EVENT_LEN;
+            events.push(Event::unpack_from_slice(
+                &buf[offset..offset + EVENT_LEN],
+                first_seq_num + i,
+            )?);
+        }
+        Ok(EventQueue {
+            account_flags,
+            head,
+            count,
+            seq_num,
+            events,
+        })
+    }
+    /// returns the unconsumed fill events
+    pub fn fills(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter().filter(|event| event.is_fill())
+    }
+    /// returns the unconsumed events of the given open orders account
+    pub fn events_for_owner<'a>(&'a self, owner: &'a Pubkey) -> impl Iterator<Item = &'a Event> {
+        self.events.iter().filter(move |event| event.owner == *owner)
+    }
+}
+
+/// tracks the sequence numbers already seen so repeated polls of an event
+/// queue only yield new events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventQueueCursor {
+    next_seq_num: u64,
+    missed: u64,
+}
+
+impl EventQueueCursor {
+    /// returns a cursor that yields every event from `next_seq_num` onwards
+    pub fn new(next_seq_num: u64) -> EventQueueCursor {
+        EventQueueCursor {
+            next_seq_num,
+            missed: 0,
+        }
+    }
+    /// returns a cursor that skips everything already in the queue
+    pub fn from_queue(queue: &EventQueue) -> EventQueueCursor {
+        EventQueueCursor::new(queue.seq_num)
+    }
+    /// sequence number of the next event the cursor has not yielded yet
+    pub fn next_seq_num(&self) -> u64 {
+        self.next_seq_num
+    }
+    /// number of events that were consumed by a crank before the cursor
+    /// could observe them
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+    /// returns the events of `queue` newer than the last one seen and
+    /// advances the cursor past them
+    pub fn poll<'a>(&mut self, queue: &'a EventQueue) -> &'a [Event] {
+        let first_seq_num = queue.seq_num.saturating_sub(queue.count);
+        if first_seq_num > self.next_seq_num {
+            self.missed += first_seq_num - self.next_seq_num;
+            self.next_seq_num = first_seq_num;
+        }
+        let skip = (self.next_seq_num - first_seq_num).min(queue.count) as usize;
+        self.next_seq_num = self.next_seq_num.max(queue.seq_num);
+        &queue.events[skip..]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serum::{ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING};
+
+    fn encode_event(flags: u8, qty: u64, owner: [u8; 32]) -> Vec<u8> {
+        let mut event = vec![flags, 3, 0, 0, 0, 0, 0, 0];
+        event.extend_from_slice(&qty.to_le_bytes());
+        event.extend_from_slice(&(qty * 2).to_le_bytes());
+        event.extend_from_slice(&7_u64.to_le_bytes());
+        event.extend_from_slice(&((42_u128 << 64) | 1).to_le_bytes());
+        event.extend_from_slice(&owner);
+        event.extend_from_slice(&99_u64.to_le_bytes());
+        event
+    }
+
+    /// builds a queue with a capacity of 3 whose events wrap around the buffer
+    fn encode_queue() -> Vec<u8> {
+        let flags = AccountFlag::Initialized as u64 | AccountFlag::EventQueue as u64;
+        let mut data = ACCOUNT_HEAD_PADDING.to_vec();
+        data.extend_from_slice(&flags.to_le_bytes());
+        // head
+        data.extend_from_slice(&2_u64.to_le_bytes());
+        // count
+        data.extend_from_slice(&2_u64.to_le_bytes());
+        // seq_num
+        data.extend_from_slice(&10_u64.to_le_bytes());
+        data.extend(encode_event(EVENT_FLAG_OUT, 3, [3; 32]));
+        data.extend(encode_event(0, 0, [0; 32]));
+        data.extend(encode_event(
+            EVENT_FLAG_FILL | EVENT_FLAG_BID | EVENT_FLAG_MAKER,
+            1,
+            [1; 32],
+        ));
+        data.extend_from_slice(ACCOUNT_TAIL_PADDING);
+        data
+    }
+
+    #[test]
+    pub fn test_unpack_event_queue() {
+        let queue = EventQueue::unpack_from_slice(&encode_queue()).unwrap();
+        assert_eq!(queue.events.len(), 2);
+        let fill = queue.events[0];
+        assert_eq!(fill.seq_num, 8);
+        assert!(fill.is_fill() && fill.maker);
+        assert_eq!(fill.side, Side::Bid);
+        assert_eq!(fill.native_coin_qty(), 1);
+        assert_eq!(fill.native_pc_qty(), 2);
+        assert_eq!(fill.native_fee_or_rebate, 7);
+        assert_eq!(fill.owner, Pubkey::new_from_array([1; 32]));
+        assert_eq!(fill.client_order_id, 99);
+        let out = queue.events[1];
+        assert_eq!(out.seq_num, 9);
+        assert_eq!(out.kind, EventKind::Out);
+        assert_eq!(out.side, Side::Ask);
+        assert_eq!(queue.fills().count(), 1);
+        assert!(EventQueue::unpack_from_slice(&encode_queue()[1..]).is_err());
+    }
+
+    #[test]
+    pub fn test_event_queue_cursor() {
+        let queue = EventQueue::unpack_from_slice(&encode_queue()).unwrap();
+        let mut cursor = EventQueueCursor::new(9);
+        assert_eq!(cursor.poll(&queue).len(), 1);
+        assert_eq!(cursor.next_seq_num(), 10);
+        assert!(cursor.poll(&queue).is_empty());
+        assert!(EventQueueCursor::from_queue(&queue).poll(&queue).is_empty());
+
+        let mut cursor = EventQueueCursor::new(5);
+        assert_eq!(cursor.poll(&queue).len(), 2);
+        assert_eq!(cursor.missed(), 3);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::errors::UtilsError;
+use crate::serum::slippage::FillEstimate;
+use crate::serum::units::{Rounding, UiAmount};
+use crate::token_mints::{msrm_token_mint, srm_token_mint};
+use anchor_client::{
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey},
+};
+use anyhow::Result;
+use serum_dex::fees::FeeTier as DexFeeTier;
+use serum_dex::matching::Side;
+use spl_token::state::Account as TokenAccount;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+/// the fee tier the dex assigns an order, based on the srm or msrm held by
+/// the fee discount account passed along with it. fees and rebates are
+/// computed by the dex's own FeeTier.
+///
+/// the market's own fee_rate_bps is a leftover of the first dex version;
+/// fills are charged according to the tier instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum FeeTier {
+    Base,
+    Srm2,
+    Srm3,
+    Srm4,
+    Srm5,
+    Srm6,
+    Msrm,
+}
+
+impl FeeTier {
+    /// returns the tier the dex assigns for the given native srm and msrm
+    /// balances
+    pub fn from_srm_and_msrm_balances(srm_held: u64, msrm_held: u64) -> FeeTier {
+        let tier = DexFeeTier::from_srm_and_msrm_balances(srm_held, msrm_held);
+        // balances only ever map to the tiers above
+        FeeTier::from_u8(tier as u8).unwrap_or(FeeTier::Base)
+    }
+    /// the smallest native srm and msrm balances the dex assigns the tier
+    /// for, so a fee discount account holding them gets this tier
+    pub fn min_srm_and_msrm_balances(self) -> (u64, u64) {
+        let one_srm = 1_000_000;
+        match self {
+            FeeTier::Base => (0, 0),
+            FeeTier::Srm2 => (100 * one_srm, 0),
+            FeeTier::Srm3 => (1_000 * one_srm, 0),
+            FeeTier::Srm4 => (10_000 * one_srm, 0),
+            FeeTier::Srm5 => (100_000 * one_srm, 0),
+            FeeTier::Srm6 => (1_000_000 * one_srm, 0),
+            FeeTier::Msrm => (0, 1),
+        }
+    }
+    /// decodes the fee tier byte stored in events and order book leaves
+    pub fn from_u8(tier: u8) -> Option<FeeTier> {
+        match tier {
+            0 => Some(FeeTier::Base),
+            1 => Some(FeeTier::Srm2),
+            2 => Some(FeeTier::Srm3),
+            3 => Some(FeeTier::Srm4),
+            4 => Some(FeeTier::Srm5),
+            5 => Some(FeeTier::Srm6),
+            6 => Some(FeeTier::Msrm),
+            _ => None,
+        }
+    }
+    /// the same tier as the dex represents it
+    pub fn to_dex(self) -> DexFeeTier {
+        DexFeeTier::try_from(self as u8)
+            .unwrap_or_else(|_| unreachable!("every tier is a dex fee tier"))
+    }
+    /// the taker fee on `native_pc_qty`, as the dex charges it
+    pub fn taker_fee(self, native_pc_qty: u64) -> u64 {
+        self.to_dex().taker_fee(native_pc_qty)
+    }
+    /// the maker rebate on `native_pc_qty`, as the dex pays it
+    pub fn maker_rebate(self, native_pc_qty: u64) -> u64 {
+        self.to_dex().maker_rebate(native_pc_qty)
+    }
+}
+
+/// loads the balances of the srm and msrm fee discount accounts and returns
+/// the resulting fee tier. without either account the tier is Base
+pub fn load_fee_tier(
+    rpc: &Arc<RpcClient>,
+    srm_account: Option<Pubkey>,
+    msrm_account: Option<Pubkey>,
+) -> Result<FeeTier> {
+    let mut keys = Vec::with_capacity(2);
+    keys.extend(srm_account);
+    keys.extend(msrm_account);
+    if keys.is_empty() {
+        return Ok(FeeTier::Base);
+    }
+    let mut accounts = rpc.get_multiple_accounts(&keys)?;
+    if accounts.len() != keys.len() {
+        return Err(UtilsError::InsufficientAccounts.into());
+    }
+    let mut accounts = accounts.iter_mut();
+    let mut srm_held = 0;
+    if srm_account.is_some() {
+        srm_held = discount_account_balance(accounts.next(), srm_token_mint(), "srm")?;
+    }
+    let mut msrm_held = 0;
+    if msrm_account.is_some() {
+        msrm_held = discount_account_balance(accounts.next(), msrm_token_mint(), "msrm")?;
+    }
+    Ok(FeeTier::from_srm_and_msrm_balances(srm_held, msrm_held))
+}
+
+fn discount_account_balance(
+    account: Option<&mut Option<Account>>,
+    mint: Pubkey,
+    name: &'static str,
+) -> Result<u64> {
+    let account = account.and_then(std::mem::take);
+    if account.is_none() {
+        return Err(UtilsError::TokenAccountISNone(name).into());
+    }
+    let token_account = TokenAccount::unpack_unchecked(&account.unwrap().data[..])?;
+    if token_account.mint != mint {
+        return Err(UtilsError::UnexpectedMint(name).into());
+    }
+    Ok(token_account.amount)
+}
+
+/// what a fill costs or pays out in pc once fees are applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FillCost {
+    pub side: Side,
+    /// pc value of the fill before fees
+    pub native_pc_qty: u64,
+    /// fee charged when taking
+    pub fee: u64,
+    /// rebate paid when making
+    pub rebate: u64,
+}
+
+impl FillCost {
+    /// pc the trader pays for a bid, or receives for an ask, after fees and
+    /// rebates
+    pub fn net_native_pc_qty(&self) -> u64 {
+        match self.side {
+            Side::Bid => self
+                .native_pc_qty
+                .saturating_add(self.fee)
+                .saturating_sub(self.rebate),
+            Side::Ask => self
+                .native_pc_qty
+                .saturating_add(self.rebate)
+                .saturating_sub(self.fee),
+        }
+    }
+}
+
+/// the cost of taking `native_pc_qty` worth of liquidity on `side`
+pub fn taker_fill_cost(tier: FeeTier, side: Side, native_pc_qty: u64) -> FillCost {
+    FillCost {
+        side,
+        native_pc_qty,
+        fee: tier.taker_fee(native_pc_qty),
+        rebate: 0,
+    }
+}
+
+/// the cost of having a resting `side` order filled for `native_pc_qty`
+pub fn maker_fill_cost(tier: FeeTier, side: Side, native_pc_qty: u64) -> FillCost {
+    FillCost {
+        side,
+        native_pc_qty,
+        fee: 0,
+        rebate: tier.maker_rebate(native_pc_qty),
+    }
+}
+
+/// the taker cost of a simulated market order
+pub fn estimated_taker_fill_cost(
+    tier: FeeTier,
+    side: Side,
+    estimate: &FillEstimate,
+    quote_token_decimals: u8,
+) -> Result<FillCost> {
+    let native_pc_qty = UiAmount::from_f64(estimate.quote_filled)?
+        .to_native(quote_token_decimals, Rounding::Nearest)?;
+    Ok(taker_fill_cost(tier, side, native_pc_qty.0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_fee_tier_from_balances() {
+        let one_srm = 1_000_000;
+        assert_eq!(FeeTier::from_srm_and_msrm_balances(0, 0), FeeTier::Base);
+        assert_eq!(
+            FeeTier::from_srm_and_msrm_balances(99 * one_srm, 0),
+            FeeTier::Base
+        );
+        assert_eq!(
+            FeeTier::from_srm_and_msrm_balances(100 * one_srm, 0),
+            FeeTier::Srm2
+        );
+        assert_eq!(
+            FeeTier::from_srm_and_msrm_balances(25_000 * one_srm, 0),
+            FeeTier::Srm4
+        );
+        assert_eq!(
+            FeeTier::from_srm_and_msrm_balances(1_000_000 * one_srm, 0),
+            FeeTier::Srm6
+        );
+        assert_eq!(FeeTier::from_srm_and_msrm_balances(0, 1), FeeTier::Msrm);
+        for tier in (0..7).filter_map(FeeTier::from_u8) {
+            let (srm_held, msrm_held) = tier.min_srm_and_msrm_balances();
+            assert_eq!(
+                FeeTier::from_srm_and_msrm_balances(srm_held, msrm_held),
+                tier
+            );
+        }
+        assert_eq!(FeeTier::from_u8(3), Some(FeeTier::Srm4));
+        assert_eq!(FeeTier::from_u8(7), None);
+    }
+
+    #[test]
+    pub fn test_fill_costs() {
+        // 10.5369 usdc at the base tier
+        let taker = taker_fill_cost(FeeTier::Base, Side::Bid, 10_536_900);
+        assert_eq!(taker.fee, 23_182);
+        assert_eq!(taker.net_native_pc_qty(), 10_560_082);
+        let taker = taker_fill_cost(FeeTier::Msrm, Side::Ask, 10_536_900);
+        assert_eq!(taker.fee, 10_537);
+        assert_eq!(taker.net_native_pc_qty(), 10_526_363);
+        let maker = maker_fill_cost(FeeTier::Base, Side::Ask, 10_536_900);
+        assert_eq!(maker.rebate, 3_161);
+        assert_eq!(maker.net_native_pc_qty(), 10_540_061);
+        let maker = maker_fill_cost(FeeTier::Base, Side::Bid, 10_536_900);
+        assert_eq!(maker.net_native_pc_qty(), 10_533_739);
+    }
+}
@@ -0,0 +1,362 @@
+use crate::errors::UtilsError;
+use crate::serum;
+use crate::serum::fees::FeeTier;
+use crate::serum::units::{MarketSpec, Rounding, UiAmount};
+use anchor_client::solana_sdk::{instruction::Instruction, pubkey::Pubkey, sysvar};
+use anchor_lang::__private::bytemuck::cast_slice;
+use anyhow::Result;
+use serum_dex::instruction::{self as dex_instruction, SelfTradeBehavior};
+use serum_dex::matching::{OrderType, Side};
+use serum_dex::state::{gen_vault_signer_key, MarketState};
+use std::convert::identity;
+use std::num::NonZeroU64;
+
+/// the accounts and lot parameters of a serum market that instructions
+/// are derived from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarketAccounts {
+    pub program_id: Pubkey,
+    pub market: Pubkey,
+    pub request_queue: Pubkey,
+    pub event_queue: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub vault_signer: Pubkey,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+}
+
+impl MarketAccounts {
+    /// derives the market accounts, including the vault signer, from a
+    /// loaded market owned by `program_id`
+    pub fn new(
+        market_state: &MarketState,
+        program_id: Pubkey,
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+    ) -> Result<MarketAccounts> {
+        let market = Pubkey::new(cast_slice(&identity(market_state.own_address) as &[_]));
+        let vault_signer =
+            gen_vault_signer_key(market_state.vault_signer_nonce, &market, &program_id)?;
+        Ok(MarketAccounts {
+            program_id,
+            market,
+            request_queue: Pubkey::new(cast_slice(&identity(market_state.req_q) as &[_])),
+            event_queue: Pubkey::new(cast_slice(&identity(market_state.event_q) as &[_])),
+            bids: Pubkey::new(cast_slice(&identity(market_state.bids) as &[_])),
+            asks: Pubkey::new(cast_slice(&identity(market_state.asks) as &[_])),
+            coin_mint: Pubkey::new(cast_slice(&identity(market_state.coin_mint) as &[_])),
+            pc_mint: Pubkey::new(cast_slice(&identity(market_state.pc_mint) as &[_])),
+            coin_vault: Pubkey::new(cast_slice(&identity(market_state.coin_vault) as &[_])),
+            pc_vault: Pubkey::new(cast_slice(&identity(market_state.pc_vault) as &[_])),
+            vault_signer,
+            coin_lot_size: market_state.coin_lot_size,
+            pc_lot_size: market_state.pc_lot_size,
+            base_token_decimals,
+            quote_token_decimals,
+        })
+    }
+    pub fn tick_size(&self) -> f64 {
+        serum::tick_size(
+            self.coin_lot_size,
+            self.pc_lot_size,
+            self.base_token_decimals,
+            self.quote_token_decimals,
+        )
+    }
+    pub fn spec(&self) -> MarketSpec {
+        MarketSpec::new(
+            self.coin_lot_size,
+            self.pc_lot_size,
+            self.base_token_decimals,
+            self.quote_token_decimals,
+        )
+    }
+    /// converts a price in quote tokens per base token into the nearest
+    /// price in quote lots per base lot
+    pub fn price_to_lots(&self, price: f64) -> Result<NonZeroU64> {
+        let price = UiAmount::from_f64(price)?;
+        let lots = self.spec().ui_to_lot_price(&price, Rounding::Nearest)?;
+        match NonZeroU64::new(lots.0) {
+            Some(lots) => Ok(lots),
+            None => Err(UtilsError::ZeroLots("price").into()),
+        }
+    }
+    /// converts a size in base tokens into base lots, rounding down
+    pub fn size_to_lots(&self, size: f64) -> Result<NonZeroU64> {
+        let size = UiAmount::from_f64(size)?;
+        let lots = self.spec().ui_to_base_lots(&size, Rounding::Down)?;
+        match NonZeroU64::new(lots) {
+            Some(lots) => Ok(lots),
+            None => Err(UtilsError::ZeroLots("size").into()),
+        }
+    }
+}
+
+/// a limit order expressed in ui units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NewOrder {
+    pub side: Side,
+    /// limit price in quote tokens per base token
+    pub price: f64,
+    /// size in base tokens
+    pub size: f64,
+    pub order_type: OrderType,
+    pub client_order_id: u64,
+    pub self_trade_behavior: SelfTradeBehavior,
+    /// maximum number of resting orders the order may match against
+    pub limit: u16,
+    /// tier of the fee discount account sent along, which the taker fee a
+    /// bid locks extra pc for is computed with. Base locks the most
+    pub fee_tier: FeeTier,
+}
+
+impl NewOrder {
+    /// returns a plain limit order that decrements the take on self trades
+    pub fn limit(side: Side, price: f64, size: f64, client_order_id: u64) -> NewOrder {
+        NewOrder {
+            side,
+            price,
+            size,
+            order_type: OrderType::Limit,
+            client_order_id,
+            self_trade_behavior: SelfTradeBehavior::DecrementTake,
+            limit: u16::MAX,
+            fee_tier: FeeTier::Base,
+        }
+    }
+    /// converts the price to the nearest tick and the size down to the lot
+    pub fn to_lot_order(&self, market: &MarketAccounts) -> Result<LotOrder> {
+        Ok(LotOrder {
+            side: self.side,
+            limit_price: market.price_to_lots(self.price)?,
+            max_coin_qty: market.size_to_lots(self.size)?,
+            order_type: self.order_type,
+            client_order_id: self.client_order_id,
+            self_trade_behavior: self.self_trade_behavior,
+            limit: self.limit,
+            fee_tier: self.fee_tier,
+        })
+    }
+}
+
+/// a limit order on the market's tick and lot grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LotOrder {
+    pub side: Side,
+    /// limit price in quote lots per base lot
+    pub limit_price: NonZeroU64,
+    /// size in base lots
+    pub max_coin_qty: NonZeroU64,
+    pub order_type: OrderType,
+    pub client_order_id: u64,
+    pub self_trade_behavior: SelfTradeBehavior,
+    /// maximum number of resting orders the order may match against
+    pub limit: u16,
+    /// tier the taker fee a bid locks extra pc for is computed with
+    pub fee_tier: FeeTier,
+}
+
+/// builds a new_order_v3 instruction. `order_payer` is the token account the
+/// order is funded from, the pc wallet for bids and the coin wallet for asks
+pub fn new_order_v3(
+    market: &MarketAccounts,
+    open_orders: &Pubkey,
+    open_orders_owner: &Pubkey,
+    order_payer: &Pubkey,
+    srm_account_referral: Option<&Pubkey>,
+    order: &NewOrder,
+) -> Result<Instruction> {
+    new_order_v3_lots(
+        market,
+        open_orders,
+        open_orders_owner,
+        order_payer,
+        srm_account_referral,
+        &order.to_lot_order(market)?,
+    )
+}
+
+/// builds a new_order_v3 instruction for an order already in lots
+pub fn new_order_v3_lots(
+    market: &MarketAccounts,
+    open_orders: &Pubkey,
+    open_orders_owner: &Pubkey,
+    order_payer: &Pubkey,
+    srm_account_referral: Option<&Pubkey>,
+    order: &LotOrder,
+) -> Result<Instruction> {
+    let max_native_pc_qty = max_native_pc_qty_including_fees(
+        order.limit_price.get(),
+        order.max_coin_qty.get(),
+        market.pc_lot_size,
+        order.fee_tier,
+    );
+    Ok(dex_instruction::new_order(
+        &market.market,
+        open_orders,
+        &market.request_queue,
+        &market.event_queue,
+        &market.bids,
+        &market.asks,
+        order_payer,
+        open_orders_owner,
+        &market.coin_vault,
+        &market.pc_vault,
+        &spl_token::id(),
+        &sysvar::rent::id(),
+        srm_account_referral,
+        &market.program_id,
+        order.side,
+        order.limit_price,
+        order.max_coin_qty,
+        order.order_type,
+        order.client_order_id,
+        order.self_trade_behavior,
+        order.limit,
+        max_native_pc_qty,
+    )?)
+}
+
+/// builds a cancel_order_v2 instruction for the order with the given id
+pub fn cancel_order_v2(
+    market: &MarketAccounts,
+    open_orders: &Pubkey,
+    open_orders_owner: &Pubkey,
+    side: Side,
+    order_id: u128,
+) -> Result<Instruction> {
+    Ok(dex_instruction::cancel_order(
+        &market.program_id,
+        &market.market,
+        &market.bids,
+        &market.asks,
+        open_orders,
+        open_orders_owner,
+        &market.event_queue,
+        side,
+        order_id,
+    )?)
+}
+
+/// builds a cancel_order_by_client_id_v2 instruction
+pub fn cancel_order_by_client_id_v2(
+    market: &MarketAccounts,
+    open_orders: &Pubkey,
+    open_orders_owner: &Pubkey,
+    client_order_id: u64,
+) -> Result<Instruction> {
+    Ok(dex_instruction::cancel_order_by_client_order_id(
+        &market.program_id,
+        &market.market,
+        &market.bids,
+        &market.asks,
+        open_orders,
+        open_orders_owner,
+        &market.event_queue,
+        client_order_id,
+    )?)
+}
+
+/// builds a settle_funds instruction moving the free balances of the open
+/// orders account into the owner's coin and pc wallets
+pub fn settle_funds(
+    market: &MarketAccounts,
+    open_orders: &Pubkey,
+    open_orders_owner: &Pubkey,
+    coin_wallet: &Pubkey,
+    pc_wallet: &Pubkey,
+    referrer_pc_wallet: Option<&Pubkey>,
+) -> Result<Instruction> {
+    Ok(dex_instruction::settle_funds(
+        &market.program_id,
+        &market.market,
+        &spl_token::id(),
+        open_orders,
+        open_orders_owner,
+        &market.coin_vault,
+        coin_wallet,
+        &market.pc_vault,
+        pc_wallet,
+        referrer_pc_wallet,
+        &market.vault_signer,
+    )?)
+}
+
+/// the pc an order of `max_coin_qty` lots at `limit_price` can lock,
+/// including the taker fee of `fee_tier` so fees never cut the order size
+pub(crate) fn max_native_pc_qty_including_fees(
+    limit_price: u64,
+    max_coin_qty: u64,
+    pc_lot_size: u64,
+    fee_tier: FeeTier,
+) -> NonZeroU64 {
+    let native_pc_qty = limit_price as u128 * max_coin_qty as u128 * pc_lot_size as u128;
+    let with_fees = if native_pc_qty > u64::MAX as u128 {
+        u64::MAX
+    } else {
+        let native_pc_qty = native_pc_qty as u64;
+        native_pc_qty.saturating_add(fee_tier.taker_fee(native_pc_qty))
+    };
+    NonZeroU64::new(with_fees).unwrap_or_else(|| NonZeroU64::new(1).unwrap())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sol_usdc() -> MarketAccounts {
+        MarketAccounts {
+            program_id: Pubkey::default(),
+            market: Pubkey::default(),
+            request_queue: Pubkey::default(),
+            event_queue: Pubkey::default(),
+            bids: Pubkey::default(),
+            asks: Pubkey::default(),
+            coin_mint: Pubkey::default(),
+            pc_mint: Pubkey::default(),
+            coin_vault: Pubkey::default(),
+            pc_vault: Pubkey::default(),
+            vault_signer: Pubkey::default(),
+            coin_lot_size: 100_000_000,
+            pc_lot_size: 100,
+            base_token_decimals: 9,
+            quote_token_decimals: 6,
+        }
+    }
+
+    #[test]
+    pub fn test_price_and_size_to_lots() {
+        let market = sol_usdc();
+        assert_eq!(market.price_to_lots(35.123).unwrap().get(), 35_123);
+        assert_eq!(market.size_to_lots(0.3).unwrap().get(), 3);
+        assert_eq!(market.size_to_lots(12.35).unwrap().get(), 123);
+        assert!(market.size_to_lots(0.05).is_err());
+        assert!(market.price_to_lots(0.0001).is_err());
+    }
+
+    #[test]
+    pub fn test_max_native_pc_qty_including_fees() {
+        // 0.3 sol at 35.123 usdc is 10.5369 usdc, plus the taker fee
+        let max_native_pc_qty = max_native_pc_qty_including_fees(35_123, 3, 100, FeeTier::Base);
+        assert_eq!(
+            max_native_pc_qty.get(),
+            10_536_900 + FeeTier::Base.taker_fee(10_536_900)
+        );
+        let max_native_pc_qty = max_native_pc_qty_including_fees(35_123, 3, 100, FeeTier::Msrm);
+        assert_eq!(
+            max_native_pc_qty.get(),
+            10_536_900 + FeeTier::Msrm.taker_fee(10_536_900)
+        );
+        assert_eq!(
+            max_native_pc_qty_including_fees(u64::MAX, 2, 100, FeeTier::Base).get(),
+            u64::MAX
+        );
+    }
+}
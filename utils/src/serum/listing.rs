@@ -0,0 +1,309 @@
+use crate::errors::UtilsError;
+use crate::serum::{self, ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING};
+use anchor_client::{
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{
+        instruction::Instruction,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_instruction,
+        sysvar::rent::Rent,
+    },
+};
+use anyhow::Result;
+use num_traits::pow::Pow;
+use rand::rngs::OsRng;
+use serum_dex::instruction as dex_instruction;
+use serum_dex::state::{gen_vault_signer_key, MarketState};
+use std::sync::Arc;
+
+/// size of a request queue account, matching the serum crank
+pub const REQUEST_QUEUE_ACCOUNT_LEN: usize = 5120 + 12;
+/// size of an event queue account, matching the serum crank
+pub const EVENT_QUEUE_ACCOUNT_LEN: usize = 262144 + 12;
+/// size of a bids or asks account, matching the serum crank
+pub const ORDER_BOOK_ACCOUNT_LEN: usize = 65536 + 12;
+/// pc dust threshold the serum crank lists markets with
+pub const DEFAULT_PC_DUST_THRESHOLD: u64 = 100;
+
+/// size of a market account, including the dex padding
+pub fn market_account_len() -> usize {
+    ACCOUNT_HEAD_PADDING.len() + std::mem::size_of::<MarketState>() + ACCOUNT_TAIL_PADDING.len()
+}
+
+/// derives the (base_lot_size, quote_lot_size) pair of a market whose price
+/// moves in steps of `tick_size` quote tokens and whose orders are multiples
+/// of `min_order_size` base tokens. this is the reverse of serum::tick_size
+pub fn lot_sizes(
+    tick_size: f64,
+    min_order_size: f64,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Result<(u64, u64)> {
+    const TEN: f64 = 10_f64;
+
+    if !tick_size.is_finite() || tick_size <= 0_f64 {
+        return Err(UtilsError::InvalidUiAmount("tick size is not finite and positive").into());
+    }
+    if !min_order_size.is_finite() || min_order_size <= 0_f64 {
+        return Err(
+            UtilsError::InvalidUiAmount("min order size is not finite and positive").into(),
+        );
+    }
+    let base_multiplier = TEN.pow(base_token_decimals as f64);
+    let quote_multiplier = TEN.pow(quote_token_decimals as f64);
+
+    let base_lot_size = (min_order_size * base_multiplier).round();
+    if base_lot_size < 1_f64 || base_lot_size > u64::MAX as f64 {
+        return Err(UtilsError::InvalidLotSize("min order size").into());
+    }
+    let base_lot_size = base_lot_size as u64;
+
+    let quote_lot_size =
+        (tick_size * base_lot_size as f64 * quote_multiplier / base_multiplier).round();
+    if quote_lot_size < 1_f64 || quote_lot_size > u64::MAX as f64 {
+        return Err(UtilsError::InvalidLotSize("tick size").into());
+    }
+    let quote_lot_size = quote_lot_size as u64;
+
+    // reject ticks that only come out right after rounding the lot size
+    let actual = serum::tick_size(
+        base_lot_size,
+        quote_lot_size,
+        base_token_decimals,
+        quote_token_decimals,
+    );
+    if ((actual - tick_size) / tick_size).abs() > 1e-9 {
+        return Err(UtilsError::InvalidLotSize("tick size").into());
+    }
+    Ok((base_lot_size, quote_lot_size))
+}
+
+/// returns the first nonce, together with the vault signer it derives, for
+/// which the market's vault signer is a valid program address
+pub fn find_vault_signer_nonce(market: &Pubkey, program_id: &Pubkey) -> Result<(u64, Pubkey)> {
+    for nonce in 0..u64::from(u8::MAX) {
+        if let Ok(vault_signer) = gen_vault_signer_key(nonce, market, program_id) {
+            return Ok((nonce, vault_signer));
+        }
+    }
+    Err(UtilsError::VaultSignerNonceNotFound.into())
+}
+
+/// parameters of a market to list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketListing {
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub pc_dust_threshold: u64,
+    /// set for permissioned markets, whose open orders need its signature
+    pub authority: Option<Pubkey>,
+    pub prune_authority: Option<Pubkey>,
+}
+
+impl MarketListing {
+    /// returns a permissionless listing with lot sizes derived from the
+    /// desired tick size and minimum order size
+    pub fn new(
+        coin_mint: Pubkey,
+        pc_mint: Pubkey,
+        tick_size: f64,
+        min_order_size: f64,
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+    ) -> Result<MarketListing> {
+        let (coin_lot_size, pc_lot_size) = lot_sizes(
+            tick_size,
+            min_order_size,
+            base_token_decimals,
+            quote_token_decimals,
+        )?;
+        Ok(MarketListing {
+            coin_mint,
+            pc_mint,
+            coin_lot_size,
+            pc_lot_size,
+            pc_dust_threshold: DEFAULT_PC_DUST_THRESHOLD,
+            authority: None,
+            prune_authority: None,
+        })
+    }
+}
+
+/// the keypairs and instructions that list a new market.
+///
+/// the instructions are split in two transactions as they do not fit in
+/// one: `vault_instructions` creates the vaults and has to be signed by the
+/// payer and both vault keypairs, `market_instructions` creates the dex
+/// accounts and initializes the market and has to be signed by the payer
+/// and the remaining keypairs. send the vault transaction first
+#[derive(Debug)]
+pub struct NewMarket {
+    pub market: Keypair,
+    pub request_queue: Keypair,
+    pub event_queue: Keypair,
+    pub bids: Keypair,
+    pub asks: Keypair,
+    pub coin_vault: Keypair,
+    pub pc_vault: Keypair,
+    pub vault_signer_nonce: u64,
+    pub vault_signer: Pubkey,
+    pub vault_instructions: Vec<Instruction>,
+    pub market_instructions: Vec<Instruction>,
+}
+
+impl NewMarket {
+    /// keypairs that sign the vault transaction, besides the payer
+    pub fn vault_signers(&self) -> Vec<&Keypair> {
+        vec![&self.coin_vault, &self.pc_vault]
+    }
+    /// keypairs that sign the market transaction, besides the payer
+    pub fn market_signers(&self) -> Vec<&Keypair> {
+        vec![
+            &self.market,
+            &self.request_queue,
+            &self.event_queue,
+            &self.bids,
+            &self.asks,
+        ]
+    }
+}
+
+/// generates the accounts of a new market and returns the instructions that
+/// allocate them and initialize the market, funded by `payer`
+pub fn create_market_instructions(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    listing: &MarketListing,
+    rent: &Rent,
+) -> Result<NewMarket> {
+    let mut csprng = OsRng {};
+    let market = Keypair::generate(&mut csprng);
+    let (vault_signer_nonce, vault_signer) = find_vault_signer_nonce(&market.pubkey(), program_id)?;
+
+    let coin_vault = Keypair::generate(&mut csprng);
+    let pc_vault = Keypair::generate(&mut csprng);
+    let mut vault_instructions = Vec::with_capacity(4);
+    for (vault, mint) in [
+        (&coin_vault, &listing.coin_mint),
+        (&pc_vault, &listing.pc_mint),
+    ]
+    .iter()
+    {
+        vault_instructions.push(system_instruction::create_account(
+            payer,
+            &vault.pubkey(),
+            rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ));
+        vault_instructions.push(spl_token::instruction::initialize_account(
+            &spl_token::id(),
+            &vault.pubkey(),
+            mint,
+            &vault_signer,
+        )?);
+    }
+
+    let request_queue = Keypair::generate(&mut csprng);
+    let event_queue = Keypair::generate(&mut csprng);
+    let bids = Keypair::generate(&mut csprng);
+    let asks = Keypair::generate(&mut csprng);
+    let dex_accounts = [
+        (&market, market_account_len()),
+        (&request_queue, REQUEST_QUEUE_ACCOUNT_LEN),
+        (&event_queue, EVENT_QUEUE_ACCOUNT_LEN),
+        (&bids, ORDER_BOOK_ACCOUNT_LEN),
+        (&asks, ORDER_BOOK_ACCOUNT_LEN),
+    ];
+    let mut market_instructions: Vec<Instruction> = dex_accounts
+        .iter()
+        .map(|(account, len)| {
+            system_instruction::create_account(
+                payer,
+                &account.pubkey(),
+                rent.minimum_balance(*len),
+                *len as u64,
+                program_id,
+            )
+        })
+        .collect();
+    market_instructions.push(dex_instruction::initialize_market(
+        &market.pubkey(),
+        program_id,
+        &listing.coin_mint,
+        &listing.pc_mint,
+        &coin_vault.pubkey(),
+        &pc_vault.pubkey(),
+        listing.authority.as_ref(),
+        listing.prune_authority.as_ref(),
+        &bids.pubkey(),
+        &asks.pubkey(),
+        &request_queue.pubkey(),
+        &event_queue.pubkey(),
+        listing.coin_lot_size,
+        listing.pc_lot_size,
+        vault_signer_nonce,
+        listing.pc_dust_threshold,
+    )?);
+
+    Ok(NewMarket {
+        market,
+        request_queue,
+        event_queue,
+        bids,
+        asks,
+        coin_vault,
+        pc_vault,
+        vault_signer_nonce,
+        vault_signer,
+        vault_instructions,
+        market_instructions,
+    })
+}
+
+/// fetches the rent sysvar and returns the instructions listing a new market
+pub fn load_create_market_instructions(
+    rpc: &Arc<RpcClient>,
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    listing: &MarketListing,
+) -> Result<NewMarket> {
+    let rent = serum::load_rent(rpc)?;
+    create_market_instructions(program_id, payer, listing, &rent)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_lot_sizes() {
+        // sol/usdc lists with a 0.001 tick and 0.1 sol minimum
+        let (base_lot_size, quote_lot_size) = lot_sizes(0.001, 0.1, 9, 6).unwrap();
+        assert_eq!(base_lot_size, 100_000_000);
+        assert_eq!(quote_lot_size, 100);
+        assert_eq!(serum::tick_size(base_lot_size, quote_lot_size, 9, 6), 0.001);
+
+        let (base_lot_size, quote_lot_size) = lot_sizes(0.0001, 1.0, 6, 6).unwrap();
+        assert_eq!(base_lot_size, 1_000_000);
+        assert_eq!(quote_lot_size, 100);
+
+        // a base lot smaller than one native unit
+        assert!(lot_sizes(0.001, 0.0000000001, 9, 6).is_err());
+        // a tick smaller than one quote lot per base lot
+        assert!(lot_sizes(0.0001, 0.001, 9, 6).is_err());
+        // a tick that is not a multiple of the quote lot
+        assert!(lot_sizes(0.00015, 0.01, 9, 6).is_err());
+        // ticks and sizes that are not finite and positive
+        assert!(lot_sizes(f64::NAN, 0.1, 9, 6).is_err());
+        assert!(lot_sizes(f64::INFINITY, 0.1, 9, 6).is_err());
+        assert!(lot_sizes(-0.001, 0.1, 9, 6).is_err());
+        assert!(lot_sizes(0.001, f64::NAN, 9, 6).is_err());
+        assert!(lot_sizes(0.001, f64::INFINITY, 9, 6).is_err());
+        assert!(lot_sizes(0.001, 0_f64, 9, 6).is_err());
+    }
+}
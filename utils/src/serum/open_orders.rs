@@ -0,0 +1,254 @@
+use crate::errors::UtilsError;
+use crate::serum::order_book::L3OrderBook;
+use crate::serum::{self, ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING};
+use anchor_client::{
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+    },
+    solana_sdk::{
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_instruction,
+        sysvar::rent::Rent,
+    },
+};
+use anchor_lang::__private::bytemuck::try_from_bytes;
+use anyhow::Result;
+use rand::rngs::OsRng;
+use serum_dex::instruction as dex_instruction;
+use serum_dex::matching::Side;
+use serum_dex::state::{AccountFlag, OpenOrders};
+use solana_account_decoder::UiAccountEncoding;
+use std::convert::identity;
+use std::sync::Arc;
+
+/// number of order slots in an open orders account
+pub const OPEN_ORDERS_SLOTS: u8 = 128;
+/// offset of the market key within an open orders account, after the
+/// head padding and account flags
+pub const OPEN_ORDERS_MARKET_OFFSET: usize = 13;
+/// offset of the owner key within an open orders account
+pub const OPEN_ORDERS_OWNER_OFFSET: usize = 45;
+
+/// size of an open orders account, including the dex padding
+pub fn open_orders_account_len() -> usize {
+    ACCOUNT_HEAD_PADDING.len() + std::mem::size_of::<OpenOrders>() + ACCOUNT_TAIL_PADDING.len()
+}
+
+/// fetches the rent sysvar and returns the lamports an open orders account
+/// needs to be rent exempt
+pub fn open_orders_rent_exemption(rpc: &Arc<RpcClient>) -> Result<u64> {
+    let rent = serum::load_rent(rpc)?;
+    Ok(rent.minimum_balance(open_orders_account_len()))
+}
+
+/// returns a new open orders keypair together with the create account and
+/// init_open_orders instructions that set it up for `owner` on `market`.
+/// the transaction has to be signed by `payer`, `owner` and the returned
+/// keypair. permissioned markets also need their `market_authority` to sign
+pub fn create_open_orders_instructions(
+    program_id: &Pubkey,
+    market: &Pubkey,
+    owner: &Pubkey,
+    payer: &Pubkey,
+    rent: &Rent,
+    market_authority: Option<&Pubkey>,
+) -> Result<(Keypair, Vec<Instruction>)> {
+    let mut csprng = OsRng {};
+    let open_orders = Keypair::generate(&mut csprng);
+    let len = open_orders_account_len();
+    let create_account = system_instruction::create_account(
+        payer,
+        &open_orders.pubkey(),
+        rent.minimum_balance(len),
+        len as u64,
+        program_id,
+    );
+    let init_open_orders = dex_instruction::init_open_orders(
+        program_id,
+        &open_orders.pubkey(),
+        owner,
+        market,
+        market_authority,
+    )?;
+    Ok((open_orders, vec![create_account, init_open_orders]))
+}
+
+/// decodes a raw open orders account, including its padding, without the
+/// rent and owner checks the dex loader makes
+pub fn unpack_open_orders(src: &[u8]) -> Result<OpenOrders> {
+    let open_orders = serum::strip_account_padding(src)
+        .and_then(|data| try_from_bytes::<OpenOrders>(data).ok())
+        .copied()
+        .ok_or(UtilsError::InvalidDexAccount("open orders"))?;
+    let expected_flags = AccountFlag::Initialized as u64 | AccountFlag::OpenOrders as u64;
+    if identity(open_orders.account_flags) & expected_flags != expected_flags {
+        return Err(UtilsError::InvalidDexAccount("open orders").into());
+    }
+    Ok(open_orders)
+}
+
+/// returns the open orders accounts `owner` has on `market`
+pub fn find_open_orders_accounts(
+    rpc: &Arc<RpcClient>,
+    program_id: &Pubkey,
+    market: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Vec<Pubkey>> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(open_orders_account_len() as u64),
+            RpcFilterType::Memcmp(Memcmp {
+                offset: OPEN_ORDERS_MARKET_OFFSET,
+                bytes: MemcmpEncodedBytes::Binary(market.to_string()),
+                encoding: None,
+            }),
+            RpcFilterType::Memcmp(Memcmp {
+                offset: OPEN_ORDERS_OWNER_OFFSET,
+                bytes: MemcmpEncodedBytes::Binary(owner.to_string()),
+                encoding: None,
+            }),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let accounts = rpc.get_program_accounts_with_config(program_id, config)?;
+    Ok(accounts.into_iter().map(|(key, _)| key).collect())
+}
+
+/// an order occupying a slot of an open orders account
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpenOrder {
+    /// index of the slot within the open orders account
+    pub slot: u8,
+    pub side: Side,
+    pub order_id: u128,
+    /// limit price of the order in quote lots per base lot
+    pub lot_price: u64,
+    pub client_order_id: u64,
+    /// remaining quantity of the order in base lots, set when a book was
+    /// supplied and the order is resting on it
+    pub lot_quantity: Option<u64>,
+    /// remaining quantity of the order in base tokens, set alongside
+    /// lot_quantity
+    pub quantity: Option<f64>,
+}
+
+impl OpenOrder {
+    /// returns the limit price in quote tokens per base token
+    pub fn price(&self, tick_size: f64) -> f64 {
+        self.lot_price as f64 * tick_size
+    }
+}
+
+/// returns an iterator over the occupied slots of an open orders account.
+/// when `book` is given each order is looked up in it to fill in the
+/// quantity still resting; orders that are not on the book (for example
+/// because they were filled but not yet consumed) are left without one
+pub fn open_orders_iter<'a>(
+    open_orders: &OpenOrders,
+    book: Option<&'a L3OrderBook>,
+) -> impl Iterator<Item = OpenOrder> + 'a {
+    // copy the fields out as the account struct is packed
+    let free_slot_bits = identity(open_orders.free_slot_bits);
+    let is_bid_bits = identity(open_orders.is_bid_bits);
+    let orders = identity(open_orders.orders);
+    let client_order_ids = identity(open_orders.client_order_ids);
+    occupied_slots(free_slot_bits, is_bid_bits, orders, client_order_ids).map(move |mut order| {
+        if let Some(resting) = book.and_then(|book| {
+            book.side(order.side)
+                .iter()
+                .find(|resting| resting.order_id == order.order_id)
+        }) {
+            order.lot_quantity = Some(resting.lot_quantity);
+            order.quantity = Some(resting.quantity);
+        }
+        order
+    })
+}
+
+fn occupied_slots(
+    free_slot_bits: u128,
+    is_bid_bits: u128,
+    orders: [u128; OPEN_ORDERS_SLOTS as usize],
+    client_order_ids: [u64; OPEN_ORDERS_SLOTS as usize],
+) -> impl Iterator<Item = OpenOrder> {
+    (0..OPEN_ORDERS_SLOTS)
+        .filter(move |slot| free_slot_bits & (1_u128 << slot) == 0)
+        .map(move |slot| {
+            let side = if is_bid_bits & (1_u128 << slot) != 0 {
+                Side::Bid
+            } else {
+                Side::Ask
+            };
+            let order_id = orders[slot as usize];
+            OpenOrder {
+                slot,
+                side,
+                order_id,
+                lot_price: (order_id >> 64) as u64,
+                client_order_id: client_order_ids[slot as usize],
+                lot_quantity: None,
+                quantity: None,
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_open_orders_account_len() {
+        assert_eq!(open_orders_account_len(), 3228);
+    }
+
+    #[test]
+    pub fn test_unpack_open_orders() {
+        let mut open_orders: OpenOrders = anchor_lang::__private::bytemuck::Zeroable::zeroed();
+        open_orders.account_flags =
+            AccountFlag::Initialized as u64 | AccountFlag::OpenOrders as u64;
+        open_orders.native_coin_total = 7;
+        open_orders.native_pc_total = 9;
+        let mut data = ACCOUNT_HEAD_PADDING.to_vec();
+        data.extend_from_slice(anchor_lang::__private::bytemuck::bytes_of(&open_orders));
+        data.extend_from_slice(ACCOUNT_TAIL_PADDING);
+        let decoded = unpack_open_orders(&data).unwrap();
+        assert_eq!(identity(decoded.native_coin_total), 7);
+        assert_eq!(identity(decoded.native_pc_total), 9);
+        assert!(unpack_open_orders(&data[..data.len() - 8]).is_err());
+        data[ACCOUNT_HEAD_PADDING.len()] = 0;
+        assert!(unpack_open_orders(&data).is_err());
+    }
+
+    #[test]
+    pub fn test_occupied_slots() {
+        let mut orders = [0_u128; OPEN_ORDERS_SLOTS as usize];
+        let mut client_order_ids = [0_u64; OPEN_ORDERS_SLOTS as usize];
+        orders[1] = (25 << 64) | 3;
+        client_order_ids[1] = 11;
+        orders[127] = (30 << 64) | 4;
+        client_order_ids[127] = 12;
+        let free_slot_bits = !((1_u128 << 1) | (1_u128 << 127));
+        let is_bid_bits = 1_u128 << 1;
+
+        let decoded: Vec<OpenOrder> =
+            occupied_slots(free_slot_bits, is_bid_bits, orders, client_order_ids).collect();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].slot, 1);
+        assert_eq!(decoded[0].side, Side::Bid);
+        assert_eq!(decoded[0].lot_price, 25);
+        assert_eq!(decoded[0].client_order_id, 11);
+        assert_eq!(decoded[1].slot, 127);
+        assert_eq!(decoded[1].side, Side::Ask);
+        assert_eq!(decoded[1].order_id, (30 << 64) | 4);
+        assert_eq!(decoded[1].price(0.5_f64), 15_f64);
+        assert!(decoded.iter().all(|order| order.lot_quantity.is_none()));
+    }
+}
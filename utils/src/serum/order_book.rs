@@ -0,0 +1,355 @@
+use crate::serum;
+use crate::serum::units::{LotPrice, MarketSpec};
+use anchor_client::{solana_client::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey};
+use anchor_lang::__private::bytemuck::cast_slice;
+use anyhow::Result;
+use serum_dex::critbit::{LeafNode, Slab, SlabView};
+use serum_dex::matching::{OrderBookState, Side};
+use serum_dex::state::MarketState;
+use std::sync::Arc;
+
+/// a single price level of an aggregated (L2) order book
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct L2Level {
+    /// price of the level in quote lots per base lot, as stored in the slab
+    pub lot_price: u64,
+    /// total quantity resting at the level in base lots
+    pub lot_quantity: u64,
+    /// price of the level in quote tokens per base token
+    pub price: f64,
+    /// total quantity resting at the level in base tokens
+    pub quantity: f64,
+}
+
+/// an aggregated snapshot of both sides of a serum order book.
+/// asks are sorted lowest price first, bids highest price first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct L2OrderBook {
+    pub asks: Vec<L2Level>,
+    pub bids: Vec<L2Level>,
+}
+
+impl L2OrderBook {
+    pub fn best_ask(&self) -> Option<&L2Level> {
+        self.asks.first()
+    }
+    pub fn best_bid(&self) -> Option<&L2Level> {
+        self.bids.first()
+    }
+    /// returns the midpoint between the best ask and best bid, if both sides
+    /// of the book have liquidity
+    pub fn mid_price(&self) -> Option<f64> {
+        let best_ask = self.best_ask()?;
+        let best_bid = self.best_bid()?;
+        Some((best_ask.price + best_bid.price) / 2_f64)
+    }
+}
+
+/// a single resting order of an order level (L3) order book
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct L3Order {
+    pub side: Side,
+    pub order_id: u128,
+    /// price of the order in quote lots per base lot
+    pub lot_price: u64,
+    /// remaining quantity of the order in base lots
+    pub lot_quantity: u64,
+    /// price of the order in quote tokens per base token
+    pub price: f64,
+    /// remaining quantity of the order in base tokens
+    pub quantity: f64,
+    /// the open orders account that placed the order
+    pub owner: Pubkey,
+    /// index of the order within the owner's open orders account
+    pub owner_slot: u8,
+    pub client_order_id: u64,
+}
+
+/// every resting order on both sides of a serum order book, in matching
+/// priority. asks are sorted lowest price first, bids highest price first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct L3OrderBook {
+    pub asks: Vec<L3Order>,
+    pub bids: Vec<L3Order>,
+}
+
+impl L3OrderBook {
+    /// returns the side of the book that holds orders of `side`
+    pub fn side(&self, side: Side) -> &[L3Order] {
+        match side {
+            Side::Ask => &self.asks,
+            Side::Bid => &self.bids,
+        }
+    }
+    /// returns all orders placed by the given open orders account
+    pub fn orders_by_owner<'a>(&'a self, owner: &'a Pubkey) -> impl Iterator<Item = &'a L3Order> {
+        self.asks
+            .iter()
+            .chain(self.bids.iter())
+            .filter(move |order| order.owner == *owner)
+    }
+    /// returns the order with the given order id, if it is still resting
+    pub fn find_order(&self, order_id: u128) -> Option<&L3Order> {
+        self.asks
+            .iter()
+            .chain(self.bids.iter())
+            .find(|order| order.order_id == order_id)
+    }
+    /// returns the orders of other owners that will be matched before the
+    /// order with the given id. returns None if the order is not in the book
+    pub fn orders_ahead_of(&self, order_id: u128) -> Option<Vec<&L3Order>> {
+        let order = self.find_order(order_id)?;
+        let orders = self.side(order.side);
+        let position = orders.iter().position(|o| o.order_id == order_id)?;
+        Some(
+            orders[..position]
+                .iter()
+                .filter(|o| o.owner != order.owner)
+                .collect(),
+        )
+    }
+}
+
+/// returns every resting order of a slab in matching priority, price first
+/// and then time. asks are walked from the lowest price up, bids from the
+/// highest price down.
+///
+/// the low 64 bits of a bid's order id hold the inverted sequence number,
+/// so sorting bids by descending order id keeps older orders ahead of newer
+/// ones at the same price, just like it does for asks sorted ascending
+pub fn slab_leaves(slab: &Slab, side: Side) -> Vec<LeafNode> {
+    let mut leaves: Vec<LeafNode> = (0..slab.capacity() as u32)
+        .filter_map(|handle| slab.get(handle))
+        .filter_map(|node| node.as_leaf())
+        .copied()
+        .collect();
+    match side {
+        Side::Ask => leaves.sort_by_key(|leaf| leaf.order_id()),
+        Side::Bid => leaves.sort_by_key(|leaf| std::cmp::Reverse(leaf.order_id())),
+    }
+    leaves
+}
+
+/// builds an L2 view of the order book with at most `depth` levels per side.
+/// lot prices and quantities are converted exactly with the market's
+/// MarketSpec
+pub fn l2_order_book(
+    order_book: &OrderBookState,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+    depth: usize,
+) -> Result<L2OrderBook> {
+    let spec = MarketSpec::from_market_state(
+        order_book.market_state,
+        base_token_decimals,
+        quote_token_decimals,
+    );
+    let to_levels = |slab: &Slab, side: Side| -> Result<Vec<L2Level>> {
+        let orders = slab_leaves(slab, side)
+            .into_iter()
+            .map(|leaf| (u64::from(leaf.price()), leaf.quantity()));
+        aggregate_levels(orders, depth)
+            .into_iter()
+            .map(|(lot_price, lot_quantity)| {
+                Ok(L2Level {
+                    lot_price,
+                    lot_quantity,
+                    price: spec.lot_price_to_ui(LotPrice(lot_price))?.to_f64(),
+                    quantity: spec.base_lots_to_ui(lot_quantity)?.to_f64(),
+                })
+            })
+            .collect()
+    };
+    Ok(L2OrderBook {
+        asks: to_levels(order_book.asks, Side::Ask)?,
+        bids: to_levels(order_book.bids, Side::Bid)?,
+    })
+}
+
+/// builds an L3 view of the order book listing every resting order
+pub fn l3_order_book(
+    order_book: &OrderBookState,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Result<L3OrderBook> {
+    let spec = MarketSpec::from_market_state(
+        order_book.market_state,
+        base_token_decimals,
+        quote_token_decimals,
+    );
+    let to_orders = |slab: &Slab, side: Side| -> Result<Vec<L3Order>> {
+        slab_leaves(slab, side)
+            .into_iter()
+            .map(|leaf| {
+                let lot_price = u64::from(leaf.price());
+                let lot_quantity = leaf.quantity();
+                Ok(L3Order {
+                    side,
+                    order_id: leaf.order_id(),
+                    lot_price,
+                    lot_quantity,
+                    price: spec.lot_price_to_ui(LotPrice(lot_price))?.to_f64(),
+                    quantity: spec.base_lots_to_ui(lot_quantity)?.to_f64(),
+                    owner: Pubkey::new(cast_slice(&leaf.owner() as &[_])),
+                    owner_slot: leaf.owner_slot(),
+                    client_order_id: leaf.client_order_id(),
+                })
+            })
+            .collect()
+    };
+    Ok(L3OrderBook {
+        asks: to_orders(order_book.asks, Side::Ask)?,
+        bids: to_orders(order_book.bids, Side::Bid)?,
+    })
+}
+
+/// fetches the asks and bids of the market and returns their L2 view
+pub fn load_l2_order_book(
+    rpc: &Arc<RpcClient>,
+    market_state: MarketState,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+    depth: usize,
+) -> Result<L2OrderBook> {
+    serum::with_order_book(rpc, market_state, |order_book| {
+        l2_order_book(order_book, base_token_decimals, quote_token_decimals, depth)
+    })
+}
+
+/// fetches the asks and bids of the market and returns their L3 view
+pub fn load_l3_order_book(
+    rpc: &Arc<RpcClient>,
+    market_state: MarketState,
+    base_token_decimals: u8,
+    quote_token_decimals: u8,
+) -> Result<L3OrderBook> {
+    serum::with_order_book(rpc, market_state, |order_book| {
+        l3_order_book(order_book, base_token_decimals, quote_token_decimals)
+    })
+}
+
+/// folds (lot price, lot quantity) pairs, already sorted in priority order,
+/// into at most `depth` price levels
+fn aggregate_levels(orders: impl Iterator<Item = (u64, u64)>, depth: usize) -> Vec<(u64, u64)> {
+    let mut levels: Vec<(u64, u64)> = Vec::new();
+    for (price, quantity) in orders {
+        match levels.last_mut() {
+            Some(level) if level.0 == price => level.1 += quantity,
+            _ => {
+                if levels.len() == depth {
+                    break;
+                }
+                levels.push((price, quantity));
+            }
+        }
+    }
+    levels
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serum::catalog::find_market;
+    use crate::serum::{load_serum_market, mainnet_serum_program_id};
+    use crate::token_mints::{
+        usdc_token_mint, wsol_token_mint, USDC_TOKEN_DECIMALS, WSOL_TOKEN_DECIMALS,
+    };
+    use anchor_client::Cluster;
+
+    #[test]
+    pub fn test_aggregate_levels() {
+        let orders = vec![(10, 1), (10, 2), (11, 5), (12, 1), (12, 1), (13, 7)];
+        let levels = aggregate_levels(orders.clone().into_iter(), 3);
+        assert_eq!(levels, vec![(10, 3), (11, 5), (12, 2)]);
+        let levels = aggregate_levels(orders.into_iter(), usize::MAX);
+        assert_eq!(levels.len(), 4);
+        assert!(aggregate_levels(vec![(1, 1)].into_iter(), 0).is_empty());
+    }
+
+    fn l3_order(side: Side, order_id: u128, owner: Pubkey) -> L3Order {
+        L3Order {
+            side,
+            order_id,
+            lot_price: (order_id >> 64) as u64,
+            lot_quantity: 1,
+            price: 0_f64,
+            quantity: 0_f64,
+            owner,
+            owner_slot: 0,
+            client_order_id: 0,
+        }
+    }
+
+    #[test]
+    pub fn test_l3_orders_ahead_of() {
+        let ours = Pubkey::new_from_array([1; 32]);
+        let theirs = Pubkey::new_from_array([2; 32]);
+        let book = L3OrderBook {
+            asks: vec![],
+            bids: vec![
+                l3_order(Side::Bid, (11 << 64) | 5, theirs),
+                l3_order(Side::Bid, (10 << 64) | 9, ours),
+                l3_order(Side::Bid, (10 << 64) | 8, theirs),
+                l3_order(Side::Bid, (10 << 64) | 7, ours),
+            ],
+        };
+        assert_eq!(book.orders_by_owner(&ours).count(), 2);
+        let ahead = book.orders_ahead_of((10 << 64) | 7).unwrap();
+        assert_eq!(ahead.len(), 2);
+        assert!(ahead.iter().all(|order| order.owner == theirs));
+        assert!(book.orders_ahead_of(42).is_none());
+    }
+
+    #[test]
+    pub fn test_load_l2_order_book() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let market = find_market(
+            &rpc,
+            mainnet_serum_program_id(),
+            wsol_token_mint(),
+            usdc_token_mint(),
+        )
+        .unwrap();
+        let market_state =
+            load_serum_market(&rpc, market.address, mainnet_serum_program_id()).unwrap();
+        let book = load_l2_order_book(
+            &rpc,
+            market_state,
+            WSOL_TOKEN_DECIMALS,
+            USDC_TOKEN_DECIMALS,
+            10,
+        )
+        .unwrap();
+        assert!(book.asks.len() <= 10 && book.bids.len() <= 10);
+        assert!(book.best_ask().unwrap().price > book.best_bid().unwrap().price);
+        println!("sol-usdc l2 book {:#?}", book);
+    }
+
+    #[test]
+    pub fn test_load_l3_order_book() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let market = find_market(
+            &rpc,
+            mainnet_serum_program_id(),
+            wsol_token_mint(),
+            usdc_token_mint(),
+        )
+        .unwrap();
+        let market_state =
+            load_serum_market(&rpc, market.address, mainnet_serum_program_id()).unwrap();
+        let book =
+            load_l3_order_book(&rpc, market_state, WSOL_TOKEN_DECIMALS, USDC_TOKEN_DECIMALS)
+                .unwrap();
+        let best_ask = book.asks.first().unwrap();
+        assert!(book.find_order(best_ask.order_id).is_some());
+        println!("sol-usdc best ask {:#?}", best_ask);
+    }
+}
@@ -0,0 +1,503 @@
+use crate::errors::UtilsError;
+use crate::serum::fees::FeeTier;
+use crate::serum::instructions::{cancel_order_v2, new_order_v3_lots, LotOrder, MarketAccounts};
+use crate::serum::open_orders::OpenOrder;
+use crate::serum::units::{MarketSpec, Rounding, UiAmount};
+use anchor_client::solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use anyhow::Result;
+use serum_dex::instruction::SelfTradeBehavior;
+use serum_dex::matching::{OrderType, Side};
+use std::num::NonZeroU64;
+
+/// how the size of a ladder level grows away from the best level, in base
+/// tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeCurve {
+    /// the same size at every level
+    Flat(f64),
+    /// `first` at the best level, plus `step` for every level after it
+    Linear { first: f64, step: f64 },
+    /// `first` at the best level, multiplied by `ratio` for every level
+    /// after it
+    Geometric { first: f64, ratio: f64 },
+}
+
+impl SizeCurve {
+    /// the size of the level at `index`, 0 being the best level
+    pub fn size(&self, index: usize) -> f64 {
+        match *self {
+            SizeCurve::Flat(size) => size,
+            SizeCurve::Linear { first, step } => first + step * index as f64,
+            SizeCurve::Geometric { first, ratio } => first * ratio.powi(index as i32),
+        }
+    }
+}
+
+/// the inputs of a two sided quote ladder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LadderParams {
+    /// fair price in quote tokens per base token
+    pub fair_price: f64,
+    /// distance between the best bid and the best ask, in bps of the fair
+    /// price
+    pub spread_bps: f64,
+    /// distance between consecutive levels of a side, in bps of the fair
+    /// price
+    pub level_spacing_bps: f64,
+    /// number of levels on each side
+    pub levels: usize,
+    pub size_curve: SizeCurve,
+    /// inventory position from -1 to 1, positive when long the base token.
+    /// a long position lowers both sides by `inventory_skew` half spreads
+    /// and moves size from the bids to the asks, a short one the opposite
+    pub inventory_skew: f64,
+}
+
+/// a single order of a ladder, on the market's tick and lot grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote {
+    pub side: Side,
+    /// price in quote lots per base lot
+    pub lot_price: u64,
+    /// size in base lots
+    pub lot_quantity: u64,
+}
+
+impl Quote {
+    /// returns the quote as a post only order
+    pub fn to_lot_order(&self, client_order_id: u64) -> Result<LotOrder> {
+        Ok(LotOrder {
+            side: self.side,
+            limit_price: NonZeroU64::new(self.lot_price).ok_or(UtilsError::ZeroLots("price"))?,
+            max_coin_qty: NonZeroU64::new(self.lot_quantity).ok_or(UtilsError::ZeroLots("size"))?,
+            order_type: OrderType::PostOnly,
+            client_order_id,
+            self_trade_behavior: SelfTradeBehavior::DecrementTake,
+            limit: u16::MAX,
+            fee_tier: FeeTier::Base,
+        })
+    }
+}
+
+/// the bids and asks of a market maker, each side best level first.
+/// bids are rounded down and asks up to the tick, sizes down to the lot,
+/// so a ladder never quotes tighter or larger than asked for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuoteLadder {
+    pub bids: Vec<Quote>,
+    pub asks: Vec<Quote>,
+}
+
+impl QuoteLadder {
+    /// builds the ladder for a market. levels that round to the same price
+    /// are merged, and levels rounding to zero lots or a non positive price
+    /// are dropped
+    pub fn new(spec: &MarketSpec, params: &LadderParams) -> Result<QuoteLadder> {
+        if !(params.fair_price.is_finite() && params.fair_price > 0_f64) {
+            return Err(UtilsError::InvalidLadder("fair price must be positive").into());
+        }
+        if !(params.spread_bps >= 0_f64 && params.level_spacing_bps >= 0_f64) {
+            return Err(
+                UtilsError::InvalidLadder("spread and spacing must not be negative").into(),
+            );
+        }
+        if !(-1_f64..=1_f64).contains(&params.inventory_skew) {
+            return Err(UtilsError::InvalidLadder("inventory skew must be within -1 and 1").into());
+        }
+        if params.levels == 0 {
+            return Err(UtilsError::InvalidLadder("at least one level is needed").into());
+        }
+        // the ladder is computed in exact decimals, so a price on the tick
+        // stays on it whatever the rounding. a bid sits at the fair price
+        // times (20000 - (1 + skew) * spread - 2 * spacing * index) / 20000,
+        // an ask at (20000 + (1 - skew) * spread + 2 * spacing * index) / 20000
+        let fair_price = UiAmount::from_f64(params.fair_price)?;
+        let spread = UiAmount::from_f64(params.spread_bps)?;
+        let spacing = UiAmount::from_f64(params.level_spacing_bps)?;
+        let skew = UiAmount::from_f64(params.inventory_skew.abs())?;
+        let one = UiAmount::new(1, 0);
+        let (one_plus_skew, one_minus_skew) = if params.inventory_skew >= 0_f64 {
+            (
+                checked(one.checked_add(&skew))?,
+                checked(one.checked_sub(&skew))?,
+            )
+        } else {
+            (
+                checked(one.checked_sub(&skew))?,
+                checked(one.checked_add(&skew))?,
+            )
+        };
+        let bid_spread = checked(one_plus_skew.checked_mul(&spread))?;
+        let ask_spread = checked(one_minus_skew.checked_mul(&spread))?;
+        let full_scale = UiAmount::new(20_000, 0);
+        let per_full_scale = UiAmount::new(5, 5);
+
+        let mut ladder = QuoteLadder::default();
+        for index in 0..params.levels {
+            let size = params.size_curve.size(index);
+            let level_spacing = checked(spacing.checked_mul(&UiAmount::new(2 * index as u128, 0)))?;
+            // a bid below zero is dropped
+            let bid_price = match full_scale
+                .checked_sub(&bid_spread)
+                .and_then(|factor| factor.checked_sub(&level_spacing))
+            {
+                Some(factor) => checked(
+                    fair_price
+                        .checked_mul(&factor)
+                        .and_then(|price| price.checked_mul(&per_full_scale)),
+                )?,
+                None => UiAmount::default(),
+            };
+            let ask_price = checked(
+                full_scale
+                    .checked_add(&ask_spread)
+                    .and_then(|factor| factor.checked_add(&level_spacing))
+                    .and_then(|factor| fair_price.checked_mul(&factor))
+                    .and_then(|price| price.checked_mul(&per_full_scale)),
+            )?;
+            push_level(
+                &mut ladder.bids,
+                Side::Bid,
+                spec.ui_to_lot_price(&bid_price, Rounding::Down)?.0,
+                size_to_lots(spec, size, &one_minus_skew)?,
+            )?;
+            push_level(
+                &mut ladder.asks,
+                Side::Ask,
+                spec.ui_to_lot_price(&ask_price, Rounding::Up)?.0,
+                size_to_lots(spec, size, &one_plus_skew)?,
+            )?;
+        }
+        // with no spread, a fair price on the tick puts both best levels on
+        // it. post only bids there would be rejected
+        if let Some(best_ask) = ladder.asks.first().map(|quote| quote.lot_price) {
+            ladder.bids.retain(|quote| quote.lot_price < best_ask);
+        }
+        Ok(ladder)
+    }
+    /// every quote of the ladder, bids first
+    pub fn quotes(&self) -> impl Iterator<Item = &Quote> {
+        self.bids.iter().chain(self.asks.iter())
+    }
+    /// builds a post only new_order_v3 instruction for every quote. client
+    /// order ids count up from `first_client_order_id`
+    pub fn new_order_instructions(
+        &self,
+        market: &MarketAccounts,
+        open_orders: &Pubkey,
+        open_orders_owner: &Pubkey,
+        coin_wallet: &Pubkey,
+        pc_wallet: &Pubkey,
+        first_client_order_id: u64,
+    ) -> Result<Vec<Instruction>> {
+        place_instructions(
+            self.quotes(),
+            market,
+            open_orders,
+            open_orders_owner,
+            coin_wallet,
+            pc_wallet,
+            first_client_order_id,
+        )
+    }
+}
+
+/// the cancels and places that turn the orders we have resting into a
+/// ladder
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reconciliation {
+    pub cancels: Vec<OpenOrder>,
+    pub places: Vec<Quote>,
+}
+
+impl Reconciliation {
+    pub fn is_empty(&self) -> bool {
+        self.cancels.is_empty() && self.places.is_empty()
+    }
+    /// builds the cancel_order_v2 instructions followed by the post only
+    /// new_order_v3 ones, so the freed slots and funds are available to the
+    /// new orders. client order ids count up from `first_client_order_id`
+    pub fn instructions(
+        &self,
+        market: &MarketAccounts,
+        open_orders: &Pubkey,
+        open_orders_owner: &Pubkey,
+        coin_wallet: &Pubkey,
+        pc_wallet: &Pubkey,
+        first_client_order_id: u64,
+    ) -> Result<Vec<Instruction>> {
+        let mut instructions = self
+            .cancels
+            .iter()
+            .map(|order| {
+                cancel_order_v2(
+                    market,
+                    open_orders,
+                    open_orders_owner,
+                    order.side,
+                    order.order_id,
+                )
+            })
+            .collect::<Result<Vec<Instruction>>>()?;
+        instructions.extend(place_instructions(
+            self.places.iter(),
+            market,
+            open_orders,
+            open_orders_owner,
+            coin_wallet,
+            pc_wallet,
+            first_client_order_id,
+        )?);
+        Ok(instructions)
+    }
+}
+
+/// compares the orders of an open orders account, decoded along with the
+/// book, against a ladder. a resting order is kept when a quote of the same
+/// side and price wants within `size_tolerance_lots` of its remaining size,
+/// which keeps its place in the queue, and cancelled otherwise. orders that
+/// are no longer on the book are left for the crank
+pub fn reconcile(
+    ladder: &QuoteLadder,
+    open_orders: &[OpenOrder],
+    size_tolerance_lots: u64,
+) -> Reconciliation {
+    let mut unmatched: Vec<&Quote> = ladder.quotes().collect();
+    let mut reconciliation = Reconciliation::default();
+    for order in open_orders {
+        let lot_quantity = match order.lot_quantity {
+            Some(lot_quantity) => lot_quantity,
+            None => continue,
+        };
+        let matching = unmatched.iter().position(|quote| {
+            quote.side == order.side
+                && quote.lot_price == order.lot_price
+                && diff(quote.lot_quantity, lot_quantity) <= size_tolerance_lots
+        });
+        match matching {
+            Some(index) => {
+                unmatched.remove(index);
+            }
+            None => reconciliation.cancels.push(*order),
+        }
+    }
+    reconciliation.places = unmatched.into_iter().copied().collect();
+    reconciliation
+}
+
+fn place_instructions<'a>(
+    quotes: impl Iterator<Item = &'a Quote>,
+    market: &MarketAccounts,
+    open_orders: &Pubkey,
+    open_orders_owner: &Pubkey,
+    coin_wallet: &Pubkey,
+    pc_wallet: &Pubkey,
+    first_client_order_id: u64,
+) -> Result<Vec<Instruction>> {
+    quotes
+        .enumerate()
+        .map(|(index, quote)| {
+            let order = quote.to_lot_order(first_client_order_id.wrapping_add(index as u64))?;
+            let order_payer = match quote.side {
+                Side::Bid => pc_wallet,
+                Side::Ask => coin_wallet,
+            };
+            new_order_v3_lots(
+                market,
+                open_orders,
+                open_orders_owner,
+                order_payer,
+                None,
+                &order,
+            )
+        })
+        .collect()
+}
+
+/// appends a level, merging it into the previous one when both round to the
+/// same price
+fn push_level(
+    quotes: &mut Vec<Quote>,
+    side: Side,
+    lot_price: u64,
+    lot_quantity: u64,
+) -> Result<()> {
+    if lot_price == 0 || lot_quantity == 0 {
+        return Ok(());
+    }
+    match quotes.last_mut() {
+        Some(last) if last.lot_price == lot_price => {
+            last.lot_quantity = last
+                .lot_quantity
+                .checked_add(lot_quantity)
+                .ok_or(UtilsError::AmountOverflow("ladder level"))?;
+        }
+        _ => quotes.push(Quote {
+            side,
+            lot_price,
+            lot_quantity,
+        }),
+    }
+    Ok(())
+}
+
+/// converts `size` times `skew_factor` into base lots, rounding down.
+/// sizes that are not positive give zero, which drops the level
+fn size_to_lots(spec: &MarketSpec, size: f64, skew_factor: &UiAmount) -> Result<u64> {
+    if size.is_nan() || size <= 0_f64 {
+        return Ok(0);
+    }
+    let size = checked(UiAmount::from_f64(size)?.checked_mul(skew_factor))?;
+    spec.ui_to_base_lots(&size, Rounding::Down)
+}
+
+fn checked(amount: Option<UiAmount>) -> Result<UiAmount> {
+    amount.ok_or_else(|| UtilsError::AmountOverflow("ladder level").into())
+}
+
+fn diff(a: u64, b: u64) -> u64 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sol_usdc() -> MarketSpec {
+        MarketSpec::new(100_000_000, 100, 9, 6)
+    }
+
+    fn params(inventory_skew: f64) -> LadderParams {
+        LadderParams {
+            fair_price: 100_f64,
+            spread_bps: 10_f64,
+            level_spacing_bps: 5_f64,
+            levels: 3,
+            size_curve: SizeCurve::Linear {
+                first: 1_f64,
+                step: 0.5_f64,
+            },
+            inventory_skew,
+        }
+    }
+
+    fn prices(quotes: &[Quote]) -> Vec<(u64, u64)> {
+        quotes
+            .iter()
+            .map(|quote| (quote.lot_price, quote.lot_quantity))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_quote_ladder() {
+        let ladder = QuoteLadder::new(&sol_usdc(), &params(0_f64)).unwrap();
+        assert_eq!(
+            prices(&ladder.bids),
+            vec![(99_950, 10), (99_900, 15), (99_850, 20)]
+        );
+        assert_eq!(
+            prices(&ladder.asks),
+            vec![(100_050, 10), (100_100, 15), (100_150, 20)]
+        );
+
+        // long inventory quotes lower and sells more than it buys
+        let ladder = QuoteLadder::new(&sol_usdc(), &params(0.5_f64)).unwrap();
+        assert_eq!(
+            prices(&ladder.bids),
+            vec![(99_925, 5), (99_875, 7), (99_825, 10)]
+        );
+        assert_eq!(
+            prices(&ladder.asks),
+            vec![(100_025, 15), (100_075, 22), (100_125, 30)]
+        );
+
+        let order = ladder.asks[0].to_lot_order(7).unwrap();
+        assert_eq!(order.limit_price.get(), 100_025);
+        assert_eq!(order.max_coin_qty.get(), 15);
+        assert_eq!(order.order_type, OrderType::PostOnly);
+
+        // a fully long book only asks
+        let ladder = QuoteLadder::new(&sol_usdc(), &params(1_f64)).unwrap();
+        assert!(ladder.bids.is_empty());
+        assert!(QuoteLadder::new(&sol_usdc(), &params(1.5_f64)).is_err());
+    }
+
+    #[test]
+    pub fn test_quote_ladder_rounding() {
+        let mut params = params(0_f64);
+        params.fair_price = 100.0004_f64;
+        params.spread_bps = 0_f64;
+        params.level_spacing_bps = 0.01_f64;
+        params.size_curve = SizeCurve::Flat(0.19_f64);
+        let ladder = QuoteLadder::new(&sol_usdc(), &params).unwrap();
+        // the levels are closer than a tick and merge into one per side
+        assert_eq!(prices(&ladder.bids), vec![(100_000, 3)]);
+        assert_eq!(prices(&ladder.asks), vec![(100_001, 3)]);
+
+        params.fair_price = 100_f64;
+        let ladder = QuoteLadder::new(&sol_usdc(), &params).unwrap();
+        assert_eq!(ladder.asks[0].lot_price, 100_000);
+        assert!(ladder.bids.iter().all(|quote| quote.lot_price < 100_000));
+
+        // merged levels whose size overflows are an error
+        let mut quotes = Vec::new();
+        push_level(&mut quotes, Side::Ask, 100_000, u64::MAX).unwrap();
+        assert!(push_level(&mut quotes, Side::Ask, 100_000, 1).is_err());
+    }
+
+    #[test]
+    pub fn test_reconcile() {
+        let ladder = QuoteLadder::new(&sol_usdc(), &params(0_f64)).unwrap();
+        let resting = |slot: u8, side: Side, lot_price: u64, lot_quantity: Option<u64>| OpenOrder {
+            slot,
+            side,
+            order_id: (lot_price as u128) << 64 | slot as u128,
+            lot_price,
+            client_order_id: slot as u64,
+            lot_quantity,
+            quantity: None,
+        };
+        let open_orders = vec![
+            // kept, partially filled within the tolerance
+            resting(0, Side::Bid, 99_950, Some(9)),
+            // cancelled, too small
+            resting(1, Side::Bid, 99_900, Some(5)),
+            // cancelled, the price left the ladder
+            resting(2, Side::Ask, 100_200, Some(10)),
+            // kept
+            resting(3, Side::Ask, 100_050, Some(10)),
+            // filled, waiting for the crank
+            resting(4, Side::Ask, 100_100, None),
+        ];
+        let reconciliation = reconcile(&ladder, &open_orders, 1);
+        let cancelled: Vec<u8> = reconciliation
+            .cancels
+            .iter()
+            .map(|order| order.slot)
+            .collect();
+        assert_eq!(cancelled, vec![1, 2]);
+        assert_eq!(reconciliation.places.len(), 4);
+        assert!(reconciliation
+            .places
+            .iter()
+            .all(|quote| quote.lot_price != 99_950 && quote.lot_price != 100_050));
+
+        let all_kept: Vec<OpenOrder> = ladder
+            .quotes()
+            .enumerate()
+            .map(|(slot, quote)| {
+                resting(
+                    slot as u8,
+                    quote.side,
+                    quote.lot_price,
+                    Some(quote.lot_quantity),
+                )
+            })
+            .collect();
+        assert!(reconcile(&ladder, &all_kept, 0).is_empty());
+    }
+}
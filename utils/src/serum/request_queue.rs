@@ -0,0 +1,266 @@
+use crate::errors::UtilsError;
+use crate::serum::strip_account_padding;
+use anchor_client::{solana_client::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey};
+use anchor_lang::__private::bytemuck::cast_slice;
+use anyhow::Result;
+use arrayref::{array_ref, array_refs};
+use serum_dex::instruction::SelfTradeBehavior;
+use serum_dex::matching::{OrderType, Side};
+use serum_dex::state::{AccountFlag, MarketState};
+use std::convert::identity;
+use std::sync::Arc;
+
+/// size of the request queue header, after the account padding
+pub const REQUEST_QUEUE_HEADER_LEN: usize = 32;
+/// size of a single request
+pub const REQUEST_LEN: usize = 80;
+
+const REQUEST_FLAG_NEW_ORDER: u8 = 0x01;
+const REQUEST_FLAG_CANCEL_ORDER: u8 = 0x02;
+const REQUEST_FLAG_BID: u8 = 0x04;
+const REQUEST_FLAG_POST_ONLY: u8 = 0x08;
+const REQUEST_FLAG_IMMEDIATE_OR_CANCEL: u8 = 0x10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    NewOrder {
+        order_type: OrderType,
+        self_trade_behavior: SelfTradeBehavior,
+        /// maximum base lots the order may trade
+        max_coin_qty: u64,
+        /// native pc locked for the order, only set for bids
+        native_pc_qty_locked: u64,
+    },
+    CancelOrder {
+        /// id the dex assigned to the cancel request
+        cancel_id: u64,
+    },
+}
+
+/// a decoded, not yet matched serum request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Request {
+    pub kind: RequestKind,
+    pub side: Side,
+    pub owner_slot: u8,
+    pub fee_tier: u8,
+    /// id of the new order, or of the order to cancel
+    pub order_id: u128,
+    /// the open orders account that sent the request
+    pub owner: Pubkey,
+    pub client_order_id: u64,
+}
+
+impl Request {
+    pub fn is_new_order(&self) -> bool {
+        matches!(self.kind, RequestKind::NewOrder { .. })
+    }
+    pub fn is_cancel_order(&self) -> bool {
+        matches!(self.kind, RequestKind::CancelOrder { .. })
+    }
+    fn unpack_from_slice(src: &[u8]) -> Result<Request> {
+        let input = array_ref![src, 0, REQUEST_LEN];
+        let (
+            request_flags,
+            owner_slot,
+            fee_tier,
+            self_trade_behavior,
+            _padding,
+            max_coin_qty_or_cancel_id,
+            native_pc_qty_locked,
+            order_id,
+            owner,
+            client_order_id,
+        ) = array_refs![input, 1, 1, 1, 1, 4, 8, 8, 16, 32, 8];
+        let request_flags = request_flags[0];
+        let max_coin_qty_or_cancel_id = u64::from_le_bytes(*max_coin_qty_or_cancel_id);
+        let kind = match (
+            request_flags & REQUEST_FLAG_NEW_ORDER != 0,
+            request_flags & REQUEST_FLAG_CANCEL_ORDER != 0,
+        ) {
+            (true, false) => {
+                let order_type = if request_flags & REQUEST_FLAG_POST_ONLY != 0 {
+                    OrderType::PostOnly
+                } else if request_flags & REQUEST_FLAG_IMMEDIATE_OR_CANCEL != 0 {
+                    OrderType::ImmediateOrCancel
+                } else {
+                    OrderType::Limit
+                };
+                let self_trade_behavior = match self_trade_behavior[0] {
+                    0 => SelfTradeBehavior::DecrementTake,
+                    1 => SelfTradeBehavior::CancelProvide,
+                    2 => SelfTradeBehavior::AbortTransaction,
+                    _ => return Err(UtilsError::InvalidDexAccount("request queue").into()),
+                };
+                RequestKind::NewOrder {
+                    order_type,
+                    self_trade_behavior,
+                    max_coin_qty: max_coin_qty_or_cancel_id,
+                    native_pc_qty_locked: u64::from_le_bytes(*native_pc_qty_locked),
+                }
+            }
+            (false, true) => RequestKind::CancelOrder {
+                cancel_id: max_coin_qty_or_cancel_id,
+            },
+            _ => return Err(UtilsError::InvalidDexAccount("request queue").into()),
+        };
+        let side = if request_flags & REQUEST_FLAG_BID != 0 {
+            Side::Bid
+        } else {
+            Side::Ask
+        };
+        Ok(Request {
+            kind,
+            side,
+            owner_slot: owner_slot[0],
+            fee_tier: fee_tier[0],
+            order_id: u128::from_le_bytes(*order_id),
+            owner: Pubkey::new_from_array(*owner),
+            client_order_id: u64::from_le_bytes(*client_order_id),
+        })
+    }
+}
+
+/// the pending contents of a serum request queue
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestQueue {
+    pub account_flags: u64,
+    /// ring buffer index of the oldest pending request
+    pub head: u64,
+    /// number of pending requests
+    pub count: u64,
+    /// sequence number the dex will assign to the next order
+    pub next_seq_num: u64,
+    /// the pending requests, oldest first
+    pub requests: Vec<Request>,
+}
+
+impl RequestQueue {
+    /// loads the request queue of the given market
+    pub fn load(rpc: &Arc<RpcClient>, market_state: &MarketState) -> Result<RequestQueue> {
+        let request_queue_key = Pubkey::new(cast_slice(&identity(market_state.req_q) as &[_]));
+        let account_data = rpc.get_account_data(&request_queue_key)?;
+        RequestQueue::unpack_from_slice(&account_data[..])
+    }
+    /// decodes a raw request queue account, including its padding
+    pub fn unpack_from_slice(src: &[u8]) -> Result<RequestQueue> {
+        let data = strip_account_padding(src);
+        if data.is_none() || data.unwrap().len() < REQUEST_QUEUE_HEADER_LEN {
+            return Err(UtilsError::InvalidDexAccount("request queue").into());
+        }
+        let data = data.unwrap();
+        let (header, buf) = data.split_at(REQUEST_QUEUE_HEADER_LEN);
+        let header = array_ref![header, 0, REQUEST_QUEUE_HEADER_LEN];
+        let (account_flags, head, count, next_seq_num) = array_refs![header, 8, 8, 8, 8];
+        let account_flags = u64::from_le_bytes(*account_flags);
+        let head = u64::from_le_bytes(*head);
+        let count = u64::from_le_bytes(*count);
+        let next_seq_num = u64::from_le_bytes(*next_seq_num);
+
+        let expected_flags = AccountFlag::Initialized as u64 | AccountFlag::RequestQueue as u64;
+        let capacity = (buf.len() / REQUEST_LEN) as u64;
+        if account_flags & expected_flags != expected_flags
+            || count > capacity
+            || (capacity > 0 && head >= capacity)
+        {
+            return Err(UtilsError::InvalidDexAccount("request queue").into());
+        }
+        let mut requests = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let offset = (((head + i) % capacity) as usize) * REQUEST_LEN;
+            requests.push(Request::unpack_from_slice(
+                &buf[offset..offset + REQUEST_LEN],
+            )?);
+        }
+        Ok(RequestQueue {
+            account_flags,
+            head,
+            count,
+            next_seq_num,
+            requests,
+        })
+    }
+    /// returns the pending requests of the given open orders account
+    pub fn requests_for_owner<'a>(
+        &'a self,
+        owner: &'a Pubkey,
+    ) -> impl Iterator<Item = &'a Request> {
+        self.requests.iter().filter(move |request| request.owner == *owner)
+    }
+    /// returns the pending new order request of `owner` with the given client
+    /// order id. if this is None and the order is not on the book either, the
+    /// order was matched away or dropped
+    pub fn find_new_order(&self, owner: &Pubkey, client_order_id: u64) -> Option<&Request> {
+        self.requests.iter().find(|request| {
+            request.is_new_order()
+                && request.owner == *owner
+                && request.client_order_id == client_order_id
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serum::{ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING};
+
+    fn encode_request(
+        flags: u8,
+        qty_or_cancel_id: u64,
+        owner: [u8; 32],
+        client_order_id: u64,
+    ) -> Vec<u8> {
+        let mut request = vec![flags, 1, 0, 1, 0, 0, 0, 0];
+        request.extend_from_slice(&qty_or_cancel_id.to_le_bytes());
+        request.extend_from_slice(&500_u64.to_le_bytes());
+        request.extend_from_slice(&((42_u128 << 64) | 1).to_le_bytes());
+        request.extend_from_slice(&owner);
+        request.extend_from_slice(&client_order_id.to_le_bytes());
+        request
+    }
+
+    #[test]
+    pub fn test_unpack_request_queue() {
+        let flags = AccountFlag::Initialized as u64 | AccountFlag::RequestQueue as u64;
+        let mut data = ACCOUNT_HEAD_PADDING.to_vec();
+        data.extend_from_slice(&flags.to_le_bytes());
+        // head
+        data.extend_from_slice(&0_u64.to_le_bytes());
+        // count
+        data.extend_from_slice(&2_u64.to_le_bytes());
+        // next_seq_num
+        data.extend_from_slice(&77_u64.to_le_bytes());
+        data.extend(encode_request(
+            REQUEST_FLAG_NEW_ORDER | REQUEST_FLAG_BID | REQUEST_FLAG_POST_ONLY,
+            10,
+            [1; 32],
+            5,
+        ));
+        data.extend(encode_request(REQUEST_FLAG_CANCEL_ORDER, 3, [2; 32], 6));
+        data.extend(encode_request(0, 0, [0; 32], 0));
+        data.extend_from_slice(ACCOUNT_TAIL_PADDING);
+
+        let queue = RequestQueue::unpack_from_slice(&data).unwrap();
+        assert_eq!(queue.next_seq_num, 77);
+        assert_eq!(queue.requests.len(), 2);
+        let new_order = queue.requests[0];
+        assert_eq!(new_order.side, Side::Bid);
+        assert_eq!(
+            new_order.kind,
+            RequestKind::NewOrder {
+                order_type: OrderType::PostOnly,
+                self_trade_behavior: SelfTradeBehavior::CancelProvide,
+                max_coin_qty: 10,
+                native_pc_qty_locked: 500,
+            }
+        );
+        let cancel = queue.requests[1];
+        assert_eq!(cancel.side, Side::Ask);
+        assert_eq!(cancel.kind, RequestKind::CancelOrder { cancel_id: 3 });
+
+        let owner = Pubkey::new_from_array([1; 32]);
+        assert!(queue.find_new_order(&owner, 5).is_some());
+        assert!(queue.find_new_order(&owner, 6).is_none());
+        assert!(RequestQueue::unpack_from_slice(&data[..10]).is_err());
+    }
+}
@@ -0,0 +1,932 @@
+//! an offline serum market for backtests.
+//!
+//! the simulator does not match orders itself. it keeps in-memory copies of
+//! a market's accounts, seeded from a captured book, and runs every request
+//! through the dex's own instruction processor, serum_dex::state::State,
+//! which matches it with serum_dex::matching::OrderBookState. the book, the
+//! open orders accounts and the events are therefore the ones the program
+//! would produce. the event queue is consumed through the dex after every
+//! request, as if a crank kept up with the market.
+//!
+//! the dex moves deposits into its vaults with token program cpis, which off
+//! chain reach the default syscall stubs and do nothing, so wallet and vault
+//! balances are left alone. a request the dex rejects is rolled back, as its
+//! transaction would be
+use crate::errors::UtilsError;
+use crate::serum::crank::{pubkey_words, ConsumeEventsCrank};
+use crate::serum::event_queue::{Event, EventQueue, EVENT_LEN, EVENT_QUEUE_HEADER_LEN};
+use crate::serum::fees::FeeTier;
+use crate::serum::instructions::{cancel_order_by_client_id_v2, cancel_order_v2, MarketAccounts};
+use crate::serum::listing::find_vault_signer_nonce;
+use crate::serum::open_orders::OPEN_ORDERS_SLOTS;
+use crate::serum::order_book::{l3_order_book, L3Order, L3OrderBook};
+use crate::serum::request_queue::{RequestQueue, REQUEST_LEN, REQUEST_QUEUE_HEADER_LEN};
+use crate::serum::units::MarketSpec;
+use crate::serum::{
+    mainnet_serum_program_id, strip_account_padding, ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING,
+};
+use crate::token_mints::{msrm_token_mint, srm_token_mint};
+use anchor_client::solana_sdk::{
+    account_info::AccountInfo, bpf_loader, instruction::Instruction, program_pack::Pack,
+    pubkey::Pubkey, rent::Rent, system_program, sysvar, sysvar::Sysvar,
+};
+use anchor_lang::__private::bytemuck::{bytes_of, try_from_bytes, Zeroable};
+use anyhow::Result;
+use serum_dex::critbit::{LeafNode, Slab};
+use serum_dex::instruction::{self as dex_instruction, SelfTradeBehavior};
+use serum_dex::matching::{OrderBookState, OrderType, Side};
+use serum_dex::state::{AccountFlag, MarketState, OpenOrders, State};
+use spl_token::state::{Account as TokenAccount, AccountState};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+
+/// size of the bids and asks accounts, the size mainnet markets allocate
+const BOOK_SIDE_ACCOUNT_LEN: usize = 65_548;
+/// events the event queue holds. it is consumed after every request, so it
+/// only has to fit the events of a single one
+const EVENT_QUEUE_CAPACITY: usize = 4_096;
+/// requests the request queue holds. new_order_v3 and the v2 cancels match
+/// right away, so the queue only hands out order sequence numbers
+const REQUEST_QUEUE_CAPACITY: usize = 16;
+/// size of the account flags every dex account starts with
+const ACCOUNT_FLAGS_LEN: usize = 8;
+
+/// a new order request, in lots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimNewOrder {
+    /// the open orders account placing the order
+    pub owner: Pubkey,
+    pub side: Side,
+    /// limit price in quote lots per base lot
+    pub lot_price: u64,
+    /// maximum quantity in base lots
+    pub max_coin_qty: u64,
+    /// maximum native pc a bid may spend, fees included. asks ignore it
+    pub max_native_pc_qty: u64,
+    pub order_type: OrderType,
+    pub self_trade_behavior: SelfTradeBehavior,
+    pub client_order_id: u64,
+}
+
+/// a request replayed against the simulator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimRequest {
+    NewOrder(SimNewOrder),
+    CancelOrder {
+        owner: Pubkey,
+        side: Side,
+        order_id: u128,
+    },
+    CancelOrderByClientId {
+        owner: Pubkey,
+        client_order_id: u64,
+    },
+}
+
+/// the result of a single request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimOutcome {
+    /// id the dex gave the new order, or id of the cancelled order
+    pub order_id: u128,
+    /// base lots the new order took from the book
+    pub filled_lots: u64,
+    /// base lots of the new order left resting on the book
+    pub posted_lots: u64,
+    /// the events the request pushed, oldest first
+    pub events: Vec<Event>,
+}
+
+/// an account the simulator keeps in memory
+#[derive(Debug, Clone)]
+struct SimAccount {
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    executable: bool,
+}
+
+impl SimAccount {
+    /// a rent exempt account holding `data`
+    fn new(owner: Pubkey, data: Vec<u8>) -> SimAccount {
+        SimAccount {
+            owner,
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            executable: false,
+        }
+    }
+}
+
+/// the accounts an open orders account trades with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SimOwner {
+    /// the signer owning the open orders account
+    authority: Pubkey,
+    coin_wallet: Pubkey,
+    pc_wallet: Pubkey,
+    /// the srm or msrm account sent along with new orders, if any
+    fee_discount: Option<Pubkey>,
+}
+
+/// an offline serum market that processes new order and cancel requests
+/// with the dex program itself
+#[derive(Debug, Clone)]
+pub struct MatchingSimulator {
+    market: MarketAccounts,
+    accounts: HashMap<Pubkey, SimAccount>,
+    /// open orders account to the accounts it trades with
+    owners: HashMap<Pubkey, SimOwner>,
+    events: Vec<Event>,
+}
+
+impl MatchingSimulator {
+    /// seeds a market with a captured book. the seeded orders keep their
+    /// ids, owners, slots and client order ids and get the Base fee tier.
+    /// new orders get sequence numbers after the newest seeded order
+    pub fn new(spec: MarketSpec, book: &L3OrderBook) -> Result<MatchingSimulator> {
+        let program_id = mainnet_serum_program_id();
+        let key = |seed: &str| Pubkey::create_with_seed(&Pubkey::default(), seed, &program_id);
+        let market = key("market")?;
+        let (vault_signer_nonce, vault_signer) = find_vault_signer_nonce(&market, &program_id)?;
+        let mut simulator = MatchingSimulator {
+            market: MarketAccounts {
+                program_id,
+                market,
+                request_queue: key("request queue")?,
+                event_queue: key("event queue")?,
+                bids: key("bids")?,
+                asks: key("asks")?,
+                coin_mint: key("coin mint")?,
+                pc_mint: key("pc mint")?,
+                coin_vault: key("coin vault")?,
+                pc_vault: key("pc vault")?,
+                vault_signer,
+                coin_lot_size: spec.coin_lot_size,
+                pc_lot_size: spec.pc_lot_size,
+                base_token_decimals: spec.base_token_decimals,
+                quote_token_decimals: spec.quote_token_decimals,
+            },
+            accounts: HashMap::new(),
+            owners: HashMap::new(),
+            events: Vec::new(),
+        };
+        simulator.insert_programs()?;
+        let (coin_vault, coin_mint) = (simulator.market.coin_vault, simulator.market.coin_mint);
+        let (pc_vault, pc_mint) = (simulator.market.pc_vault, simulator.market.pc_mint);
+        simulator.insert_token_account(coin_vault, coin_mint, vault_signer, 0)?;
+        simulator.insert_token_account(pc_vault, pc_mint, vault_signer, 0)?;
+
+        let next_seq_num = book
+            .asks
+            .iter()
+            .chain(book.bids.iter())
+            .map(|order| order_seq_num(order.side, order.order_id).saturating_add(1))
+            .max()
+            .unwrap_or(0);
+        simulator.insert_dex_account(
+            simulator.market.request_queue,
+            queue_account(
+                AccountFlag::RequestQueue,
+                REQUEST_QUEUE_HEADER_LEN,
+                REQUEST_LEN * REQUEST_QUEUE_CAPACITY,
+                next_seq_num,
+            ),
+        );
+        simulator.insert_dex_account(
+            simulator.market.event_queue,
+            queue_account(
+                AccountFlag::EventQueue,
+                EVENT_QUEUE_HEADER_LEN,
+                EVENT_LEN * EVENT_QUEUE_CAPACITY,
+                0,
+            ),
+        );
+        simulator.insert_dex_account(
+            simulator.market.asks,
+            book_side_account(AccountFlag::Asks, &book.asks)?,
+        );
+        simulator.insert_dex_account(
+            simulator.market.bids,
+            book_side_account(AccountFlag::Bids, &book.bids)?,
+        );
+
+        let mut orders_by_owner: HashMap<Pubkey, Vec<&L3Order>> = HashMap::new();
+        for order in book.asks.iter().chain(book.bids.iter()) {
+            orders_by_owner.entry(order.owner).or_default().push(order);
+        }
+        let (mut coin_deposits_total, mut pc_deposits_total) = (0_u64, 0_u64);
+        for (owner, orders) in orders_by_owner.iter() {
+            let (native_coin_locked, native_pc_locked) = simulator.insert_owner(*owner, orders)?;
+            coin_deposits_total = coin_deposits_total.saturating_add(native_coin_locked);
+            pc_deposits_total = pc_deposits_total.saturating_add(native_pc_locked);
+        }
+
+        let mut market_state: MarketState = Zeroable::zeroed();
+        market_state.account_flags = AccountFlag::Initialized as u64 | AccountFlag::Market as u64;
+        market_state.own_address = pubkey_words(&market);
+        market_state.vault_signer_nonce = vault_signer_nonce;
+        market_state.coin_mint = pubkey_words(&coin_mint);
+        market_state.pc_mint = pubkey_words(&pc_mint);
+        market_state.coin_vault = pubkey_words(&coin_vault);
+        market_state.pc_vault = pubkey_words(&pc_vault);
+        market_state.coin_deposits_total = coin_deposits_total;
+        market_state.pc_deposits_total = pc_deposits_total;
+        market_state.req_q = pubkey_words(&simulator.market.request_queue);
+        market_state.event_q = pubkey_words(&simulator.market.event_queue);
+        market_state.bids = pubkey_words(&simulator.market.bids);
+        market_state.asks = pubkey_words(&simulator.market.asks);
+        market_state.coin_lot_size = spec.coin_lot_size;
+        market_state.pc_lot_size = spec.pc_lot_size;
+        simulator.insert_dex_account(market, padded(bytes_of(&market_state)));
+        Ok(simulator)
+    }
+    /// gives an owner's new orders the fee tier, by sending a fee discount
+    /// account holding the tier's srm or msrm along with them. owners start
+    /// at Base. orders already resting keep the tier they were placed with
+    pub fn set_fee_tier(&mut self, owner: Pubkey, fee_tier: FeeTier) -> Result<()> {
+        let mut sim_owner = self.owner(&owner)?;
+        sim_owner.fee_discount = match fee_tier.min_srm_and_msrm_balances() {
+            (0, 0) => None,
+            (srm_held, msrm_held) => {
+                let (mint, amount) = if msrm_held > 0 {
+                    (msrm_token_mint(), msrm_held)
+                } else {
+                    (srm_token_mint(), srm_held)
+                };
+                let key = Pubkey::create_with_seed(&owner, "fee discount", &spl_token::id())?;
+                self.insert_token_account(key, mint, sim_owner.authority, amount)?;
+                Some(key)
+            }
+        };
+        self.owners.insert(owner, sim_owner);
+        Ok(())
+    }
+    /// returns the resting orders, read from the dex's bids and asks
+    pub fn book(&self) -> Result<L3OrderBook> {
+        let mut market_state = self.market_state()?;
+        let mut asks = self.account(&self.market.asks)?.data.clone();
+        let mut bids = self.account(&self.market.bids)?.data.clone();
+        let order_book = OrderBookState {
+            market_state: &mut market_state,
+            asks: slab_mut(&mut asks),
+            bids: slab_mut(&mut bids),
+        };
+        l3_order_book(
+            &order_book,
+            self.market.base_token_decimals,
+            self.market.quote_token_decimals,
+        )
+    }
+    /// the sequence number the id of the next new order is made from
+    pub fn next_order_seq_num(&self) -> Result<u64> {
+        let request_queue = self.account(&self.market.request_queue)?;
+        Ok(RequestQueue::unpack_from_slice(&request_queue.data[..])?.next_seq_num)
+    }
+    /// every event pushed since the simulator was created or last drained
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+    /// processes the requests in order and returns their outcomes. stops at
+    /// the first request the dex rejects
+    pub fn replay(&mut self, requests: &[SimRequest]) -> Result<Vec<SimOutcome>> {
+        requests
+            .iter()
+            .map(|request| match request {
+                SimRequest::NewOrder(order) => self.new_order(order),
+                SimRequest::CancelOrder {
+                    owner,
+                    side,
+                    order_id,
+                } => self.cancel_order(owner, *side, *order_id),
+                SimRequest::CancelOrderByClientId {
+                    owner,
+                    client_order_id,
+                } => self.cancel_order_by_client_id(owner, *client_order_id),
+            })
+            .collect()
+    }
+    /// sends a new_order_v3 instruction, which matches the order against the
+    /// book and posts what is left of it as its order type allows
+    pub fn new_order(&mut self, order: &SimNewOrder) -> Result<SimOutcome> {
+        let limit_price = NonZeroU64::new(order.lot_price).ok_or(UtilsError::ZeroLots("price"))?;
+        let max_coin_qty =
+            NonZeroU64::new(order.max_coin_qty).ok_or(UtilsError::ZeroLots("size"))?;
+        let max_native_pc_qty =
+            NonZeroU64::new(order.max_native_pc_qty).ok_or(UtilsError::ZeroLots("pc quantity"))?;
+        let sim_owner = self.owner(&order.owner)?;
+        let order_payer = match order.side {
+            Side::Bid => sim_owner.pc_wallet,
+            Side::Ask => sim_owner.coin_wallet,
+        };
+        // the id the dex derives from the price and the next sequence number
+        let seq_num = self.next_order_seq_num()?;
+        let order_id = match order.side {
+            Side::Ask => ((order.lot_price as u128) << 64) | seq_num as u128,
+            Side::Bid => ((order.lot_price as u128) << 64) | (!seq_num) as u128,
+        };
+        let instruction = dex_instruction::new_order(
+            &self.market.market,
+            &order.owner,
+            &self.market.request_queue,
+            &self.market.event_queue,
+            &self.market.bids,
+            &self.market.asks,
+            &order_payer,
+            &sim_owner.authority,
+            &self.market.coin_vault,
+            &self.market.pc_vault,
+            &spl_token::id(),
+            &sysvar::rent::id(),
+            sim_owner.fee_discount.as_ref(),
+            &self.market.program_id,
+            order.side,
+            limit_price,
+            max_coin_qty,
+            order.order_type,
+            order.client_order_id,
+            order.self_trade_behavior,
+            u16::MAX,
+            max_native_pc_qty,
+        )?;
+        let events = self.process(&instruction)?;
+        let filled_lots = events
+            .iter()
+            .filter(|event| event.is_fill() && !event.maker && event.order_id == order_id)
+            .map(|event| event.native_coin_qty() / self.market.coin_lot_size)
+            .sum();
+        let posted_lots = self
+            .book()?
+            .find_order(order_id)
+            .map_or(0, |order| order.lot_quantity);
+        Ok(SimOutcome {
+            order_id,
+            filled_lots,
+            posted_lots,
+            events,
+        })
+    }
+    /// sends a cancel_order_v2 instruction for a resting order of `owner`
+    pub fn cancel_order(
+        &mut self,
+        owner: &Pubkey,
+        side: Side,
+        order_id: u128,
+    ) -> Result<SimOutcome> {
+        let resting = self
+            .book()?
+            .side(side)
+            .iter()
+            .any(|order| order.order_id == order_id && order.owner == *owner);
+        if !resting {
+            return Err(UtilsError::OrderNotFound(order_id).into());
+        }
+        let authority = self.owner(owner)?.authority;
+        let instruction = cancel_order_v2(&self.market, owner, &authority, side, order_id)?;
+        let events = self.process(&instruction)?;
+        Ok(SimOutcome {
+            order_id,
+            events,
+            ..SimOutcome::default()
+        })
+    }
+    /// sends a cancel_order_by_client_id_v2 instruction for the resting order
+    /// of `owner` with the given client order id
+    pub fn cancel_order_by_client_id(
+        &mut self,
+        owner: &Pubkey,
+        client_order_id: u64,
+    ) -> Result<SimOutcome> {
+        let order = self
+            .book()?
+            .orders_by_owner(owner)
+            .find(|order| order.client_order_id == client_order_id)
+            .copied();
+        let order_id = match order {
+            Some(order) => order.order_id,
+            None => return Err(UtilsError::OrderNotFound(client_order_id as u128).into()),
+        };
+        let authority = self.owner(owner)?.authority;
+        let instruction =
+            cancel_order_by_client_id_v2(&self.market, owner, &authority, client_order_id)?;
+        let events = self.process(&instruction)?;
+        Ok(SimOutcome {
+            order_id,
+            events,
+            ..SimOutcome::default()
+        })
+    }
+
+    /// runs an instruction through the dex and consumes the events it
+    /// pushed, returning them. if either fails, every account is restored
+    /// as the failed transaction would leave it
+    fn process(&mut self, instruction: &Instruction) -> Result<Vec<Event>> {
+        let snapshot = self.accounts.clone();
+        let result = self
+            .execute(instruction)
+            .and_then(|_| self.consume_events());
+        if result.is_err() {
+            self.accounts = snapshot;
+        }
+        let events = result?;
+        self.events.extend_from_slice(&events);
+        Ok(events)
+    }
+    fn execute(&mut self, instruction: &Instruction) -> Result<()> {
+        let mut account_infos = HashMap::new();
+        for (key, account) in self.accounts.iter_mut() {
+            let meta = instruction.accounts.iter().find(|meta| meta.pubkey == *key);
+            if let Some(meta) = meta {
+                account_infos.insert(
+                    *key,
+                    AccountInfo::new(
+                        key,
+                        meta.is_signer,
+                        meta.is_writable,
+                        &mut account.lamports,
+                        &mut account.data[..],
+                        &account.owner,
+                        account.executable,
+                        0,
+                    ),
+                );
+            }
+        }
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                account_infos
+                    .get(&meta.pubkey)
+                    .cloned()
+                    .ok_or_else(|| UtilsError::InsufficientAccounts.into())
+            })
+            .collect::<Result<Vec<AccountInfo>>>()?;
+        State::process(&instruction.program_id, &accounts, &instruction.data)?;
+        Ok(())
+    }
+    /// consumes every pending event with consume_events instructions, the
+    /// way a crank would
+    fn consume_events(&mut self) -> Result<Vec<Event>> {
+        let event_queue = self.account(&self.market.event_queue)?;
+        let event_queue = EventQueue::unpack_from_slice(&event_queue.data[..])?;
+        let crank = ConsumeEventsCrank {
+            program_id: self.market.program_id,
+            market: self.market.market,
+            event_queue: self.market.event_queue,
+            // the dex moves no funds into them, so the vaults will do
+            coin_fee_receivable: self.market.coin_vault,
+            pc_fee_receivable: self.market.pc_vault,
+            max_open_orders_per_instruction: self.owners.len(),
+        };
+        for instruction in crank.instructions(&event_queue)? {
+            self.execute(&instruction)?;
+        }
+        Ok(event_queue.events)
+    }
+    fn account(&self, key: &Pubkey) -> Result<&SimAccount> {
+        self.accounts
+            .get(key)
+            .ok_or_else(|| UtilsError::InsufficientAccounts.into())
+    }
+    fn market_state(&self) -> Result<MarketState> {
+        let market = self.account(&self.market.market)?;
+        strip_account_padding(&market.data[..])
+            .and_then(|data| try_from_bytes::<MarketState>(data).ok())
+            .copied()
+            .ok_or_else(|| UtilsError::InvalidDexAccount("market").into())
+    }
+    /// returns the accounts of an open orders account, creating an empty one
+    /// the first time it is seen
+    fn owner(&mut self, open_orders: &Pubkey) -> Result<SimOwner> {
+        if let Some(sim_owner) = self.owners.get(open_orders) {
+            return Ok(*sim_owner);
+        }
+        self.insert_owner(*open_orders, &[])?;
+        Ok(self.owners[open_orders])
+    }
+    /// creates an open orders account holding `orders`, along with its
+    /// authority and funded wallets. returns the native coin and pc the
+    /// orders lock
+    fn insert_owner(&mut self, open_orders: Pubkey, orders: &[&L3Order]) -> Result<(u64, u64)> {
+        let sim_owner = SimOwner {
+            authority: Pubkey::create_with_seed(&open_orders, "authority", &system_program::id())?,
+            coin_wallet: Pubkey::create_with_seed(&open_orders, "coin wallet", &spl_token::id())?,
+            pc_wallet: Pubkey::create_with_seed(&open_orders, "pc wallet", &spl_token::id())?,
+            fee_discount: None,
+        };
+        let mut open_orders_state: OpenOrders = Zeroable::zeroed();
+        open_orders_state.account_flags =
+            AccountFlag::Initialized as u64 | AccountFlag::OpenOrders as u64;
+        open_orders_state.market = pubkey_words(&self.market.market);
+        open_orders_state.owner = pubkey_words(&sim_owner.authority);
+        let mut free_slot_bits = u128::MAX;
+        let mut is_bid_bits = 0_u128;
+        let mut order_ids = [0_u128; OPEN_ORDERS_SLOTS as usize];
+        let mut client_order_ids = [0_u64; OPEN_ORDERS_SLOTS as usize];
+        let (mut native_coin_locked, mut native_pc_locked) = (0_u64, 0_u64);
+        for order in orders {
+            let slot_bit = 1_u128
+                .checked_shl(order.owner_slot as u32)
+                .filter(|slot_bit| free_slot_bits & slot_bit != 0)
+                .ok_or(UtilsError::InvalidDexAccount("open orders"))?;
+            free_slot_bits &= !slot_bit;
+            order_ids[order.owner_slot as usize] = order.order_id;
+            client_order_ids[order.owner_slot as usize] = order.client_order_id;
+            match order.side {
+                Side::Ask => {
+                    native_coin_locked = order
+                        .lot_quantity
+                        .saturating_mul(self.market.coin_lot_size)
+                        .saturating_add(native_coin_locked);
+                }
+                Side::Bid => {
+                    is_bid_bits |= slot_bit;
+                    native_pc_locked = order
+                        .lot_quantity
+                        .saturating_mul(order.lot_price)
+                        .saturating_mul(self.market.pc_lot_size)
+                        .saturating_add(native_pc_locked);
+                }
+            }
+        }
+        open_orders_state.native_coin_total = native_coin_locked;
+        open_orders_state.native_pc_total = native_pc_locked;
+        open_orders_state.free_slot_bits = free_slot_bits;
+        open_orders_state.is_bid_bits = is_bid_bits;
+        open_orders_state.orders = order_ids;
+        open_orders_state.client_order_ids = client_order_ids;
+        self.insert_dex_account(open_orders, padded(bytes_of(&open_orders_state)));
+        self.accounts.insert(
+            sim_owner.authority,
+            SimAccount::new(system_program::id(), Vec::new()),
+        );
+        let (coin_mint, pc_mint) = (self.market.coin_mint, self.market.pc_mint);
+        self.insert_token_account(
+            sim_owner.coin_wallet,
+            coin_mint,
+            sim_owner.authority,
+            u64::MAX,
+        )?;
+        self.insert_token_account(sim_owner.pc_wallet, pc_mint, sim_owner.authority, u64::MAX)?;
+        self.owners.insert(open_orders, sim_owner);
+        Ok((native_coin_locked, native_pc_locked))
+    }
+    /// adds the token program and the rent sysvar new orders reference
+    fn insert_programs(&mut self) -> Result<()> {
+        let mut token_program = SimAccount::new(bpf_loader::id(), Vec::new());
+        token_program.executable = true;
+        self.accounts.insert(spl_token::id(), token_program);
+        let mut rent = SimAccount::new(sysvar::id(), vec![0; Rent::size_of()]);
+        let rent_key = sysvar::rent::id();
+        let mut rent_info = AccountInfo::new(
+            &rent_key,
+            false,
+            false,
+            &mut rent.lamports,
+            &mut rent.data[..],
+            &rent.owner,
+            false,
+            0,
+        );
+        Rent::default()
+            .to_account_info(&mut rent_info)
+            .ok_or(UtilsError::RentAccountIsNone)?;
+        self.accounts.insert(rent_key, rent);
+        Ok(())
+    }
+    fn insert_dex_account(&mut self, key: Pubkey, data: Vec<u8>) {
+        self.accounts
+            .insert(key, SimAccount::new(self.market.program_id, data));
+    }
+    fn insert_token_account(
+        &mut self,
+        key: Pubkey,
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    ) -> Result<()> {
+        let token_account = TokenAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        };
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(token_account, &mut data[..])?;
+        self.accounts
+            .insert(key, SimAccount::new(spl_token::id(), data));
+        Ok(())
+    }
+}
+
+/// the sequence number encoded in the low 64 bits of an order id, which
+/// bids store inverted
+fn order_seq_num(side: Side, order_id: u128) -> u64 {
+    match side {
+        Side::Ask => order_id as u64,
+        Side::Bid => !(order_id as u64),
+    }
+}
+
+/// wraps the bytes of a dex account in its head and tail padding
+fn padded(bytes: &[u8]) -> Vec<u8> {
+    let mut data = ACCOUNT_HEAD_PADDING.to_vec();
+    data.extend_from_slice(bytes);
+    data.extend_from_slice(ACCOUNT_TAIL_PADDING);
+    data
+}
+
+/// a zeroed dex account of `len` bytes, padding included, flagged as an
+/// initialized account of the given kind
+fn dex_account(kind: AccountFlag, len: usize) -> Vec<u8> {
+    let mut data = vec![0; len - ACCOUNT_HEAD_PADDING.len() - ACCOUNT_TAIL_PADDING.len()];
+    let account_flags = AccountFlag::Initialized as u64 | kind as u64;
+    data[..ACCOUNT_FLAGS_LEN].copy_from_slice(&account_flags.to_le_bytes());
+    padded(&data)
+}
+
+/// an empty request or event queue whose next item gets `seq_num`
+fn queue_account(kind: AccountFlag, header_len: usize, buf_len: usize, seq_num: u64) -> Vec<u8> {
+    let len = ACCOUNT_HEAD_PADDING.len() + header_len + buf_len + ACCOUNT_TAIL_PADDING.len();
+    let mut data = dex_account(kind, len);
+    // the sequence number is the last field of both queue headers
+    let seq_num_offset = ACCOUNT_HEAD_PADDING.len() + header_len - 8;
+    data[seq_num_offset..seq_num_offset + 8].copy_from_slice(&seq_num.to_le_bytes());
+    data
+}
+
+/// a bids or asks account holding the given orders
+fn book_side_account(kind: AccountFlag, orders: &[L3Order]) -> Result<Vec<u8>> {
+    let mut data = dex_account(kind, BOOK_SIDE_ACCOUNT_LEN);
+    let slab = slab_mut(&mut data);
+    for order in orders {
+        let leaf = LeafNode::new(
+            order.owner_slot,
+            order.order_id,
+            pubkey_words(&order.owner),
+            order.lot_quantity,
+            FeeTier::Base.to_dex(),
+            order.client_order_id,
+        );
+        slab.insert_leaf(&leaf)
+            .map_err(|_| UtilsError::InvalidDexAccount("order book"))?;
+    }
+    Ok(data)
+}
+
+/// the slab of a bids or asks account, behind its padding and flags
+fn slab_mut(data: &mut [u8]) -> &mut Slab {
+    let start = ACCOUNT_HEAD_PADDING.len() + ACCOUNT_FLAGS_LEN;
+    let end = data.len() - ACCOUNT_TAIL_PADDING.len();
+    Slab::new(&mut data[start..end])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serum::event_queue::EventKind;
+    use crate::serum::instructions::max_native_pc_qty_including_fees;
+    use crate::serum::tick_size;
+
+    fn spec() -> MarketSpec {
+        MarketSpec::new(100_000_000, 100, 9, 6)
+    }
+
+    fn limit(owner: Pubkey, side: Side, lot_price: u64, max_coin_qty: u64) -> SimNewOrder {
+        SimNewOrder {
+            owner,
+            side,
+            lot_price,
+            max_coin_qty,
+            max_native_pc_qty: max_native_pc_qty_including_fees(
+                lot_price,
+                max_coin_qty,
+                spec().pc_lot_size,
+                FeeTier::Base,
+            )
+            .get(),
+            order_type: OrderType::Limit,
+            self_trade_behavior: SelfTradeBehavior::DecrementTake,
+            client_order_id: 0,
+        }
+    }
+
+    fn empty() -> MatchingSimulator {
+        MatchingSimulator::new(spec(), &L3OrderBook::default()).unwrap()
+    }
+
+    #[test]
+    pub fn test_price_time_priority_and_fills() {
+        let (alice, bob, carol) = (
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_from_array([2; 32]),
+            Pubkey::new_from_array([3; 32]),
+        );
+        let mut simulator = empty();
+        let first = simulator
+            .new_order(&limit(alice, Side::Ask, 101, 2))
+            .unwrap();
+        let second = simulator.new_order(&limit(bob, Side::Ask, 100, 1)).unwrap();
+        let third = simulator
+            .new_order(&limit(alice, Side::Ask, 100, 1))
+            .unwrap();
+        assert_eq!(first.posted_lots, 2);
+        assert!(simulator.drain_events().is_empty());
+        let book = simulator.book().unwrap();
+        let ids: Vec<u128> = book.asks.iter().map(|order| order.order_id).collect();
+        assert_eq!(ids, vec![second.order_id, third.order_id, first.order_id]);
+
+        // takes bob's and then alice's order at 100, then one lot at 101
+        let take = simulator
+            .new_order(&limit(carol, Side::Bid, 101, 3))
+            .unwrap();
+        assert_eq!(take.filled_lots, 3);
+        assert_eq!(take.posted_lots, 0);
+        let maker_fills: Vec<&Event> = take
+            .events
+            .iter()
+            .filter(|event| event.is_fill() && event.maker)
+            .collect();
+        assert_eq!(maker_fills.len(), 3);
+        assert_eq!(maker_fills[0].owner, bob);
+        assert_eq!(maker_fills[1].order_id, third.order_id);
+        assert_eq!(maker_fills[2].order_id, first.order_id);
+        assert_eq!(maker_fills[0].native_qty_paid, 100_000_000);
+        // one 0.1 sol lot for 10_000 native pc, 0.01 usdc, plus the rebate
+        assert_eq!(
+            maker_fills[0].native_qty_released,
+            10_000 + FeeTier::Base.maker_rebate(10_000)
+        );
+        let taker = take
+            .events
+            .iter()
+            .find(|event| event.is_fill() && !event.maker)
+            .unwrap();
+        assert_eq!(taker.native_qty_released, 300_000_000);
+        assert_eq!(taker.native_fee_or_rebate, FeeTier::Base.taker_fee(30_100));
+        assert_eq!(
+            taker.native_qty_paid,
+            30_100 + FeeTier::Base.taker_fee(30_100)
+        );
+        let book = simulator.book().unwrap();
+        assert_eq!(book.asks.len(), 1);
+        assert_eq!(book.asks[0].lot_quantity, 1);
+    }
+
+    #[test]
+    pub fn test_seeded_book() {
+        let (dave, carol) = (
+            Pubkey::new_from_array([4; 32]),
+            Pubkey::new_from_array([3; 32]),
+        );
+        let seeded = L3Order {
+            side: Side::Ask,
+            order_id: (100 << 64) | 41,
+            lot_price: 100,
+            lot_quantity: 2,
+            price: 100_f64 * tick_size(100_000_000, 100, 9, 6),
+            quantity: 0.2,
+            owner: dave,
+            owner_slot: 5,
+            client_order_id: 9,
+        };
+        let book = L3OrderBook {
+            asks: vec![seeded],
+            bids: Vec::new(),
+        };
+        let mut simulator = MatchingSimulator::new(spec(), &book).unwrap();
+        assert_eq!(simulator.book().unwrap().asks[0].order_id, seeded.order_id);
+        assert_eq!(simulator.next_order_seq_num().unwrap(), 42);
+
+        let take = simulator
+            .new_order(&limit(carol, Side::Bid, 100, 1))
+            .unwrap();
+        assert_eq!(take.order_id, (100 << 64) | !42_u64 as u128);
+        let maker = take
+            .events
+            .iter()
+            .find(|event| event.is_fill() && event.maker)
+            .unwrap();
+        assert_eq!(maker.owner, dave);
+        assert_eq!(maker.owner_slot, 5);
+        assert_eq!(maker.client_order_id, 9);
+        assert_eq!(simulator.book().unwrap().asks[0].lot_quantity, 1);
+    }
+
+    #[test]
+    pub fn test_order_types_and_cancels() {
+        let (alice, bob) = (
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_from_array([2; 32]),
+        );
+        let mut simulator = empty();
+        let resting = simulator
+            .new_order(&limit(alice, Side::Bid, 99, 5))
+            .unwrap();
+
+        let mut post_only = limit(bob, Side::Ask, 99, 1);
+        post_only.order_type = OrderType::PostOnly;
+        let dropped = simulator.new_order(&post_only).unwrap();
+        assert_eq!(dropped.filled_lots, 0);
+        assert_eq!(dropped.posted_lots, 0);
+        assert_eq!(simulator.book().unwrap().bids[0].lot_quantity, 5);
+
+        let mut ioc = limit(bob, Side::Ask, 99, 8);
+        ioc.order_type = OrderType::ImmediateOrCancel;
+        let outcome = simulator.new_order(&ioc).unwrap();
+        assert_eq!(outcome.filled_lots, 5);
+        assert_eq!(outcome.posted_lots, 0);
+        assert!(simulator.book().unwrap().bids.is_empty());
+        assert!(simulator
+            .cancel_order(&alice, Side::Bid, resting.order_id)
+            .is_err());
+
+        let mut order = limit(alice, Side::Ask, 105, 2);
+        order.client_order_id = 7;
+        let placed = simulator.new_order(&order).unwrap();
+        let cancel = simulator.cancel_order_by_client_id(&alice, 7).unwrap();
+        assert_eq!(cancel.order_id, placed.order_id);
+        let out = cancel
+            .events
+            .iter()
+            .find(|event| event.kind == EventKind::Out && event.order_id == placed.order_id)
+            .unwrap();
+        assert!(out.release_funds);
+        assert_eq!(out.native_qty_released, 200_000_000);
+        assert!(simulator.book().unwrap().asks.is_empty());
+    }
+
+    #[test]
+    pub fn test_self_trade_behaviors() {
+        let alice = Pubkey::new_from_array([1; 32]);
+        let mut simulator = empty();
+        simulator
+            .new_order(&limit(alice, Side::Ask, 100, 3))
+            .unwrap();
+
+        let mut abort = limit(alice, Side::Bid, 100, 1);
+        abort.self_trade_behavior = SelfTradeBehavior::AbortTransaction;
+        assert!(simulator.new_order(&abort).is_err());
+
+        let decrement = simulator
+            .new_order(&limit(alice, Side::Bid, 100, 1))
+            .unwrap();
+        assert_eq!(decrement.filled_lots, 0);
+        assert_eq!(decrement.posted_lots, 0);
+        assert_eq!(simulator.book().unwrap().asks[0].lot_quantity, 2);
+
+        let mut cancel_provide = limit(alice, Side::Bid, 100, 1);
+        cancel_provide.self_trade_behavior = SelfTradeBehavior::CancelProvide;
+        let outcome = simulator.new_order(&cancel_provide).unwrap();
+        assert_eq!(outcome.posted_lots, 1);
+        let book = simulator.book().unwrap();
+        assert!(book.asks.is_empty());
+        assert_eq!(book.bids.len(), 1);
+    }
+
+    #[test]
+    pub fn test_abort_transaction_rolls_back() {
+        let (alice, bob) = (
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_from_array([2; 32]),
+        );
+        let mut simulator = empty();
+        simulator.new_order(&limit(bob, Side::Ask, 100, 1)).unwrap();
+        simulator
+            .new_order(&limit(alice, Side::Ask, 101, 1))
+            .unwrap();
+        let book = simulator.book().unwrap();
+        let seq_num = simulator.next_order_seq_num().unwrap();
+        let events = simulator.events().len();
+
+        // fills bob's level, then aborts on alice's own ask
+        let mut abort = limit(alice, Side::Bid, 101, 2);
+        abort.self_trade_behavior = SelfTradeBehavior::AbortTransaction;
+        assert!(simulator.new_order(&abort).is_err());
+        assert_eq!(simulator.book().unwrap(), book);
+        assert_eq!(simulator.next_order_seq_num().unwrap(), seq_num);
+        assert_eq!(simulator.events().len(), events);
+    }
+
+    #[test]
+    pub fn test_max_native_pc_qty() {
+        let (bob, carol) = (
+            Pubkey::new_from_array([2; 32]),
+            Pubkey::new_from_array([3; 32]),
+        );
+        let mut simulator = empty();
+        simulator.new_order(&limit(bob, Side::Ask, 100, 3)).unwrap();
+
+        // 15_000 native usdc, fees included, buys a single 10_000 usdc lot
+        let mut bid = limit(carol, Side::Bid, 100, 3);
+        bid.order_type = OrderType::ImmediateOrCancel;
+        bid.max_native_pc_qty = 15_000;
+        let outcome = simulator.new_order(&bid).unwrap();
+        assert_eq!(outcome.filled_lots, 1);
+        assert_eq!(simulator.book().unwrap().asks[0].lot_quantity, 2);
+    }
+}
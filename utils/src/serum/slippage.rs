@@ -0,0 +1,196 @@
+use crate::errors::UtilsError;
+use crate::serum::order_book::{L2Level, L2OrderBook};
+use anyhow::Result;
+use serum_dex::matching::Side;
+
+/// the size of a simulated market order, in ui units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderSize {
+    /// an amount of the base token to buy or sell
+    Base(f64),
+    /// an amount of the quote token to spend or receive
+    Quote(f64),
+}
+
+/// the expected outcome of a market order walking the book
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FillEstimate {
+    /// volume weighted average price of the fill
+    pub average_price: f64,
+    /// price of the last level the order reached
+    pub worst_price: f64,
+    /// base tokens that can be filled
+    pub base_filled: f64,
+    /// quote tokens that can be filled
+    pub quote_filled: f64,
+    /// false if the book ran out of liquidity before the full size was filled
+    pub fully_filled: bool,
+    /// how much worse the average price is than the mid price, as a fraction
+    /// of the mid price. 0.01 means the order pays 1% over mid when buying or
+    /// receives 1% under mid when selling
+    pub price_impact: f64,
+}
+
+/// simulates a market order of `size` against an L2 snapshot. `side` is the
+/// side of the taker, so a bid walks up the asks and an ask walks down the bids.
+/// a size that is not finite and positive is an error
+pub fn estimate_market_order(
+    book: &L2OrderBook,
+    side: Side,
+    size: OrderSize,
+) -> Result<FillEstimate> {
+    let amount = match size {
+        OrderSize::Base(base) => base,
+        OrderSize::Quote(quote) => quote,
+    };
+    if !(amount.is_finite() && amount > 0_f64) {
+        return Err(UtilsError::InvalidOrderSize(amount).into());
+    }
+    let mid_price = match (book.best_ask(), book.best_bid()) {
+        (None, _) => return Err(UtilsError::FindAsksMin.into()),
+        (_, None) => return Err(UtilsError::FindBidsMax.into()),
+        (Some(ask), Some(bid)) => (ask.price + bid.price) / 2_f64,
+    };
+    let levels = match side {
+        Side::Bid => &book.asks,
+        Side::Ask => &book.bids,
+    };
+    let mut estimate = walk_levels(levels, size)?;
+    estimate.price_impact = match side {
+        Side::Bid => (estimate.average_price - mid_price) / mid_price,
+        Side::Ask => (mid_price - estimate.average_price) / mid_price,
+    };
+    Ok(estimate)
+}
+
+/// consumes levels in order until `size` is filled or the levels run out.
+/// the returned estimate has no price impact set. a level reached with a
+/// price that is not finite and positive is an error
+fn walk_levels(levels: &[L2Level], size: OrderSize) -> Result<FillEstimate> {
+    let mut base_filled = 0_f64;
+    let mut quote_filled = 0_f64;
+    let mut worst_price = levels.first().map(|level| level.price).unwrap_or(0_f64);
+    let mut fully_filled = false;
+    for level in levels {
+        let (remaining, available) = match size {
+            OrderSize::Base(base) => (base - base_filled, level.quantity),
+            OrderSize::Quote(quote) => (quote - quote_filled, level.quantity * level.price),
+        };
+        if remaining <= 0_f64 {
+            fully_filled = true;
+            break;
+        }
+        if !(level.price.is_finite() && level.price > 0_f64) {
+            return Err(UtilsError::InvalidLevelPrice(level.price).into());
+        }
+        let taken = remaining.min(available);
+        match size {
+            OrderSize::Base(_) => {
+                base_filled += taken;
+                quote_filled += taken * level.price;
+            }
+            OrderSize::Quote(_) => {
+                base_filled += taken / level.price;
+                quote_filled += taken;
+            }
+        }
+        worst_price = level.price;
+        if taken < available {
+            fully_filled = true;
+            break;
+        }
+    }
+    if !fully_filled {
+        fully_filled = match size {
+            OrderSize::Base(base) => base_filled >= base,
+            OrderSize::Quote(quote) => quote_filled >= quote,
+        };
+    }
+    let average_price = if base_filled > 0_f64 {
+        quote_filled / base_filled
+    } else {
+        worst_price
+    };
+    Ok(FillEstimate {
+        average_price,
+        worst_price,
+        base_filled,
+        quote_filled,
+        fully_filled,
+        price_impact: 0_f64,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn level(price: f64, quantity: f64) -> L2Level {
+        L2Level {
+            lot_price: 0,
+            lot_quantity: 0,
+            price,
+            quantity,
+        }
+    }
+
+    fn book() -> L2OrderBook {
+        L2OrderBook {
+            asks: vec![level(101_f64, 1_f64), level(102_f64, 2_f64), level(104_f64, 1_f64)],
+            bids: vec![level(99_f64, 1_f64), level(98_f64, 3_f64)],
+        }
+    }
+
+    #[test]
+    pub fn test_estimate_buy_base() {
+        let estimate = estimate_market_order(&book(), Side::Bid, OrderSize::Base(2_f64)).unwrap();
+        assert!(estimate.fully_filled);
+        assert_eq!(estimate.base_filled, 2_f64);
+        assert_eq!(estimate.quote_filled, 203_f64);
+        assert_eq!(estimate.average_price, 101.5_f64);
+        assert_eq!(estimate.worst_price, 102_f64);
+        assert_eq!(estimate.price_impact, 1.5_f64 / 100_f64);
+    }
+
+    #[test]
+    pub fn test_estimate_sell_quote() {
+        let estimate =
+            estimate_market_order(&book(), Side::Ask, OrderSize::Quote(197_f64)).unwrap();
+        assert!(estimate.fully_filled);
+        assert_eq!(estimate.base_filled, 2_f64);
+        assert_eq!(estimate.worst_price, 98_f64);
+        assert_eq!(estimate.average_price, 98.5_f64);
+        assert_eq!(estimate.price_impact, 1.5_f64 / 100_f64);
+    }
+
+    #[test]
+    pub fn test_estimate_insufficient_liquidity() {
+        let estimate = estimate_market_order(&book(), Side::Bid, OrderSize::Base(10_f64)).unwrap();
+        assert!(!estimate.fully_filled);
+        assert_eq!(estimate.base_filled, 4_f64);
+        assert_eq!(estimate.worst_price, 104_f64);
+        let empty = L2OrderBook {
+            asks: vec![],
+            bids: book().bids,
+        };
+        assert!(estimate_market_order(&empty, Side::Ask, OrderSize::Base(1_f64)).is_err());
+    }
+
+    #[test]
+    pub fn test_estimate_invalid_level_price() {
+        let mut book = book();
+        book.bids.push(level(0_f64, 5_f64));
+        // the zero priced level is never reached
+        assert!(estimate_market_order(&book, Side::Ask, OrderSize::Quote(197_f64)).is_ok());
+        assert!(estimate_market_order(&book, Side::Ask, OrderSize::Quote(1_000_f64)).is_err());
+        assert!(estimate_market_order(&book, Side::Ask, OrderSize::Base(5_f64)).is_err());
+    }
+
+    #[test]
+    pub fn test_estimate_invalid_size() {
+        for size in [f64::NAN, f64::INFINITY, -1_f64, 0_f64].iter() {
+            assert!(estimate_market_order(&book(), Side::Bid, OrderSize::Base(*size)).is_err());
+            assert!(estimate_market_order(&book(), Side::Ask, OrderSize::Quote(*size)).is_err());
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::serum;
+use anchor_client::{solana_client::rpc_client::RpcClient, solana_sdk::pubkey::Pubkey};
+use anchor_lang::__private::bytemuck::cast_slice;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serum_dex::state::{gen_vault_signer_key, MarketState};
+use std::convert::identity;
+use std::fmt;
+use std::sync::Arc;
+
+/// the keys and totals of a serum market in a printable and serializable
+/// form. keys serialize as base58 strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketSummary {
+    #[serde(with = "crate::pubkey_serde")]
+    pub market: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub program_id: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub asks: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub bids: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub coin_mint: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub pc_mint: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub coin_vault: Pubkey,
+    pub coin_deposits_total: u64,
+    pub coin_fees_accrued: u64,
+    #[serde(with = "crate::pubkey_serde")]
+    pub pc_vault: Pubkey,
+    pub pc_deposits_total: u64,
+    pub pc_fees_accrued: u64,
+    pub pc_dust_threshold: u64,
+    #[serde(with = "crate::pubkey_serde")]
+    pub request_queue: Pubkey,
+    #[serde(with = "crate::pubkey_serde")]
+    pub event_queue: Pubkey,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub fee_rate_bps: u64,
+    pub referrer_rebates_accrued: u64,
+    #[serde(with = "crate::pubkey_serde")]
+    pub vault_signer: Pubkey,
+}
+
+impl MarketSummary {
+    /// summarizes a market owned by `serum_program_id`, which the vault
+    /// signer is derived with
+    pub fn new(
+        market: &MarketState,
+        market_key: Pubkey,
+        serum_program_id: Pubkey,
+    ) -> Result<MarketSummary> {
+        let vault_signer =
+            gen_vault_signer_key(market.vault_signer_nonce, &market_key, &serum_program_id)?;
+        Ok(MarketSummary {
+            market: market_key,
+            program_id: serum_program_id,
+            asks: Pubkey::new(cast_slice(&identity(market.asks) as &[_])),
+            bids: Pubkey::new(cast_slice(&identity(market.bids) as &[_])),
+            coin_mint: Pubkey::new(cast_slice(&identity(market.coin_mint) as &[_])),
+            pc_mint: Pubkey::new(cast_slice(&identity(market.pc_mint) as &[_])),
+            coin_vault: Pubkey::new(cast_slice(&identity(market.coin_vault) as &[_])),
+            coin_deposits_total: market.coin_deposits_total,
+            coin_fees_accrued: market.coin_fees_accrued,
+            pc_vault: Pubkey::new(cast_slice(&identity(market.pc_vault) as &[_])),
+            pc_deposits_total: market.pc_deposits_total,
+            pc_fees_accrued: market.pc_fees_accrued,
+            pc_dust_threshold: market.pc_dust_threshold,
+            request_queue: Pubkey::new(cast_slice(&identity(market.req_q) as &[_])),
+            event_queue: Pubkey::new(cast_slice(&identity(market.event_q) as &[_])),
+            coin_lot_size: market.coin_lot_size,
+            pc_lot_size: market.pc_lot_size,
+            fee_rate_bps: market.fee_rate_bps,
+            referrer_rebates_accrued: market.referrer_rebates_accrued,
+            vault_signer,
+        })
+    }
+    /// loads the market and summarizes it
+    pub fn load(
+        rpc: &Arc<RpcClient>,
+        market_key: Pubkey,
+        serum_program_id: Pubkey,
+    ) -> Result<MarketSummary> {
+        let market_state = serum::load_serum_market(rpc, market_key, serum_program_id)?;
+        MarketSummary::new(&market_state, market_key, serum_program_id)
+    }
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+    pub fn from_json(json: &str) -> Result<MarketSummary> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+impl fmt::Display for MarketSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "market {}", self.market)?;
+        writeln!(f, "program_id {}", self.program_id)?;
+        writeln!(f, "asks_key {}", self.asks)?;
+        writeln!(f, "bids_key {}", self.bids)?;
+        writeln!(f, "coin_mint {}", self.coin_mint)?;
+        writeln!(f, "pc_mint {}", self.pc_mint)?;
+        writeln!(f, "coin_vault {}", self.coin_vault)?;
+        writeln!(f, "coin_deposits_total {}", self.coin_deposits_total)?;
+        writeln!(f, "coin_fees_accrued {}", self.coin_fees_accrued)?;
+        writeln!(f, "pc_vault {}", self.pc_vault)?;
+        writeln!(f, "pc_deposits_total {}", self.pc_deposits_total)?;
+        writeln!(f, "pc_fees_accrued {}", self.pc_fees_accrued)?;
+        writeln!(f, "pc_dust_threshold {}", self.pc_dust_threshold)?;
+        writeln!(f, "request_queue {}", self.request_queue)?;
+        writeln!(f, "event_queue {}", self.event_queue)?;
+        writeln!(f, "coin_lot_size {}", self.coin_lot_size)?;
+        writeln!(f, "pc_lot_size {}", self.pc_lot_size)?;
+        writeln!(f, "fee_rate_bps {}", self.fee_rate_bps)?;
+        writeln!(
+            f,
+            "referrer_rebates_accrued {}",
+            self.referrer_rebates_accrued
+        )?;
+        write!(f, "vault_signer {}", self.vault_signer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::serum::catalog::find_market;
+    use crate::serum::{devnet_serum_program_id, mainnet_serum_program_id};
+    use crate::token_mints::{usdc_token_mint, wsol_token_mint};
+    use anchor_client::Cluster;
+
+    #[test]
+    pub fn test_load_market_summary() {
+        let cluster = Cluster::Custom(
+            "https://api.mainnet-beta.solana.com".to_string(),
+            "ws://api.mainnet-beta.solana.com".to_string(),
+        );
+        let rpc = Arc::new(RpcClient::new(cluster.url().to_string()));
+        let sol_usdc_market = find_market(
+            &rpc,
+            mainnet_serum_program_id(),
+            wsol_token_mint(),
+            usdc_token_mint(),
+        )
+        .unwrap()
+        .address;
+        let market_state =
+            serum::load_serum_market(&rpc, sol_usdc_market, mainnet_serum_program_id()).unwrap();
+        let summary =
+            MarketSummary::new(&market_state, sol_usdc_market, mainnet_serum_program_id())
+                .unwrap();
+        println!("{}", summary);
+
+        let json = summary.to_json().unwrap();
+        assert!(json.contains(&format!("\"coin_mint\": \"{}\"", summary.coin_mint)));
+        assert_eq!(MarketSummary::from_json(&json).unwrap(), summary);
+
+        // the mainnet nonce need not be valid for another program, so derive
+        // one for the devnet program before summarizing under it
+        let mut devnet_market_state = market_state;
+        devnet_market_state.vault_signer_nonce = (0..u64::MAX)
+            .find(|nonce| {
+                gen_vault_signer_key(*nonce, &sol_usdc_market, &devnet_serum_program_id())
+                    .is_ok()
+            })
+            .unwrap();
+        let devnet_summary = MarketSummary::new(
+            &devnet_market_state,
+            sol_usdc_market,
+            devnet_serum_program_id(),
+        )
+        .unwrap();
+        assert_eq!(
+            devnet_summary.vault_signer,
+            gen_vault_signer_key(
+                identity(devnet_market_state.vault_signer_nonce),
+                &sol_usdc_market,
+                &devnet_serum_program_id()
+            )
+            .unwrap()
+        );
+        assert_ne!(devnet_summary.vault_signer, summary.vault_signer);
+    }
+}
@@ -0,0 +1,496 @@
+use crate::errors::UtilsError;
+use anyhow::Result;
+use serum_dex::state::MarketState;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// the scale ratios without an exact decimal representation are rounded to
+pub const MAX_UI_SCALE: u8 = 18;
+
+/// how to round when a conversion drops precision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+    /// rounds half up
+    Nearest,
+}
+
+/// a price in quote lots per base lot, as encoded in order ids
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LotPrice(pub u64);
+
+/// an amount in the smallest unit of a token
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NativeAmount(pub u64);
+
+/// an exact decimal amount in ui units, worth `mantissa / 10^scale`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UiAmount {
+    pub mantissa: u128,
+    pub scale: u8,
+}
+
+impl UiAmount {
+    pub fn new(mantissa: u128, scale: u8) -> UiAmount {
+        UiAmount { mantissa, scale }
+    }
+    /// converts a float through its shortest decimal representation, so that
+    /// 0.1 becomes exactly 0.1 rather than the nearest binary fraction
+    pub fn from_f64(value: f64) -> Result<UiAmount> {
+        if !value.is_finite() || value < 0_f64 {
+            return Err(UtilsError::InvalidUiAmount("not a finite non-negative number").into());
+        }
+        Ok(value.to_string().parse::<UiAmount>()?)
+    }
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 / 10_f64.powi(self.scale as i32)
+    }
+    /// returns the ui amount of `amount` native units of a token with the
+    /// given decimals
+    pub fn from_native(amount: NativeAmount, decimals: u8) -> UiAmount {
+        UiAmount::new(amount.0 as u128, decimals)
+    }
+    /// returns the native units of a token with the given decimals
+    pub fn to_native(&self, decimals: u8, rounding: Rounding) -> Result<NativeAmount> {
+        Ok(NativeAmount(to_u64(
+            self.rescale(decimals, rounding)?,
+            "native amount",
+        )?))
+    }
+    /// returns the mantissa expressed at `scale`, rounding when digits are
+    /// dropped
+    pub fn rescale(&self, scale: u8, rounding: Rounding) -> Result<u128> {
+        match scale.cmp(&self.scale) {
+            Ordering::Equal => Ok(self.mantissa),
+            Ordering::Greater => product(&[self.mantissa, pow10(scale - self.scale)?], "ui amount"),
+            Ordering::Less => Ok(div_round(
+                self.mantissa,
+                pow10(self.scale - scale)?,
+                rounding,
+            )),
+        }
+    }
+    /// the exact sum, None on overflow
+    pub fn checked_add(&self, other: &UiAmount) -> Option<UiAmount> {
+        let (a, b, scale) = aligned(self, other)?;
+        Some(UiAmount::new(a.checked_add(b)?, scale).normalize())
+    }
+    /// the exact difference, None when `other` is larger or on overflow
+    pub fn checked_sub(&self, other: &UiAmount) -> Option<UiAmount> {
+        let (a, b, scale) = aligned(self, other)?;
+        Some(UiAmount::new(a.checked_sub(b)?, scale).normalize())
+    }
+    /// the exact product, None on overflow
+    pub fn checked_mul(&self, other: &UiAmount) -> Option<UiAmount> {
+        let mantissa = self.mantissa.checked_mul(other.mantissa)?;
+        let scale = self.scale.checked_add(other.scale)?;
+        Some(UiAmount::new(mantissa, scale).normalize())
+    }
+    /// returns the same amount without trailing zeros
+    pub fn normalize(&self) -> UiAmount {
+        let mut normalized = *self;
+        while normalized.scale > 0 && normalized.mantissa % 10 == 0 {
+            normalized.mantissa /= 10;
+            normalized.scale -= 1;
+        }
+        normalized
+    }
+}
+
+impl PartialEq for UiAmount {
+    fn eq(&self, other: &UiAmount) -> bool {
+        let (a, b) = (self.normalize(), other.normalize());
+        a.mantissa == b.mantissa && a.scale == b.scale
+    }
+}
+
+impl Eq for UiAmount {}
+
+impl fmt::Display for UiAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.mantissa);
+        }
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", self.mantissa, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}.{}", integer, fraction)
+    }
+}
+
+impl FromStr for UiAmount {
+    type Err = UtilsError<'static>;
+
+    fn from_str(s: &str) -> std::result::Result<UiAmount, Self::Err> {
+        let s = s.trim();
+        let (integer, fraction) = match s.find('.') {
+            Some(dot) => (&s[..dot], &s[dot + 1..]),
+            None => (s, ""),
+        };
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(UtilsError::InvalidUiAmount("empty"));
+        }
+        if !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+        {
+            return Err(UtilsError::InvalidUiAmount("not a decimal number"));
+        }
+        if fraction.len() > 38 {
+            return Err(UtilsError::InvalidUiAmount("too many decimals"));
+        }
+        let mantissa = format!("{}{}", integer, fraction)
+            .parse::<u128>()
+            .map_err(|_| UtilsError::InvalidUiAmount("too large"))?;
+        Ok(UiAmount::new(mantissa, fraction.len() as u8))
+    }
+}
+
+/// the lot sizes and token decimals of a market, which every conversion
+/// between lots, native and ui units depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketSpec {
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub base_token_decimals: u8,
+    pub quote_token_decimals: u8,
+}
+
+impl MarketSpec {
+    pub fn new(
+        coin_lot_size: u64,
+        pc_lot_size: u64,
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+    ) -> MarketSpec {
+        MarketSpec {
+            coin_lot_size,
+            pc_lot_size,
+            base_token_decimals,
+            quote_token_decimals,
+        }
+    }
+    pub fn from_market_state(
+        market_state: &MarketState,
+        base_token_decimals: u8,
+        quote_token_decimals: u8,
+    ) -> MarketSpec {
+        MarketSpec::new(
+            market_state.coin_lot_size,
+            market_state.pc_lot_size,
+            base_token_decimals,
+            quote_token_decimals,
+        )
+    }
+    /// the ui price of a single lot price step
+    pub fn tick_size(&self) -> Result<UiAmount> {
+        self.lot_price_to_ui(LotPrice(1))
+    }
+    /// converts a lot price into quote tokens per base token. exact unless
+    /// the market's tick has no finite decimal representation, in which
+    /// case the price is rounded to MAX_UI_SCALE decimals
+    pub fn lot_price_to_ui(&self, price: LotPrice) -> Result<UiAmount> {
+        let num = product(
+            &[
+                price.0 as u128,
+                self.pc_lot_size as u128,
+                pow10(self.base_token_decimals)?,
+            ],
+            "lot price",
+        )?;
+        let den = product(
+            &[
+                self.coin_lot_size as u128,
+                pow10(self.quote_token_decimals)?,
+            ],
+            "lot price",
+        )?;
+        ratio_to_ui(num, den)
+    }
+    /// converts a price in quote tokens per base token into a lot price
+    pub fn ui_to_lot_price(&self, price: &UiAmount, rounding: Rounding) -> Result<LotPrice> {
+        let num = product(
+            &[
+                price.mantissa,
+                self.coin_lot_size as u128,
+                pow10(self.quote_token_decimals)?,
+            ],
+            "ui price",
+        )?;
+        let den = product(
+            &[
+                pow10(price.scale)?,
+                self.pc_lot_size as u128,
+                pow10(self.base_token_decimals)?,
+            ],
+            "ui price",
+        )?;
+        if den == 0 {
+            return Err(UtilsError::ZeroLotSize.into());
+        }
+        Ok(LotPrice(to_u64(
+            div_round(num, den, rounding),
+            "lot price",
+        )?))
+    }
+    pub fn base_lots_to_native(&self, lots: u64) -> Result<NativeAmount> {
+        match lots.checked_mul(self.coin_lot_size) {
+            Some(native) => Ok(NativeAmount(native)),
+            None => Err(UtilsError::AmountOverflow("base lots").into()),
+        }
+    }
+    pub fn native_to_base_lots(&self, amount: NativeAmount, rounding: Rounding) -> Result<u64> {
+        if self.coin_lot_size == 0 {
+            return Err(UtilsError::ZeroLotSize.into());
+        }
+        to_u64(
+            div_round(amount.0 as u128, self.coin_lot_size as u128, rounding),
+            "base lots",
+        )
+    }
+    pub fn base_lots_to_ui(&self, lots: u64) -> Result<UiAmount> {
+        Ok(self.native_base_to_ui(self.base_lots_to_native(lots)?))
+    }
+    /// converts a size in base tokens into base lots in a single rounding step
+    pub fn ui_to_base_lots(&self, size: &UiAmount, rounding: Rounding) -> Result<u64> {
+        let num = product(
+            &[size.mantissa, pow10(self.base_token_decimals)?],
+            "ui size",
+        )?;
+        let den = product(&[pow10(size.scale)?, self.coin_lot_size as u128], "ui size")?;
+        if den == 0 {
+            return Err(UtilsError::ZeroLotSize.into());
+        }
+        to_u64(div_round(num, den, rounding), "base lots")
+    }
+    pub fn native_base_to_ui(&self, amount: NativeAmount) -> UiAmount {
+        UiAmount::from_native(amount, self.base_token_decimals)
+    }
+    pub fn native_quote_to_ui(&self, amount: NativeAmount) -> UiAmount {
+        UiAmount::from_native(amount, self.quote_token_decimals)
+    }
+    pub fn ui_to_native_base(&self, amount: &UiAmount, rounding: Rounding) -> Result<NativeAmount> {
+        amount.to_native(self.base_token_decimals, rounding)
+    }
+    pub fn ui_to_native_quote(
+        &self,
+        amount: &UiAmount,
+        rounding: Rounding,
+    ) -> Result<NativeAmount> {
+        amount.to_native(self.quote_token_decimals, rounding)
+    }
+    /// the native quote amount `base_lots` are worth at `price`, before fees
+    pub fn native_quote_qty(&self, price: LotPrice, base_lots: u64) -> Result<NativeAmount> {
+        let native = product(
+            &[price.0 as u128, base_lots as u128, self.pc_lot_size as u128],
+            "quote amount",
+        )?;
+        Ok(NativeAmount(to_u64(native, "quote amount")?))
+    }
+}
+
+/// returns num / den as a decimal, exact when the reduced denominator only
+/// has factors of two and five
+fn ratio_to_ui(num: u128, den: u128) -> Result<UiAmount> {
+    if den == 0 {
+        return Err(UtilsError::ZeroLotSize.into());
+    }
+    let divisor = gcd(num, den);
+    let (num, den) = (num / divisor, den / divisor);
+    let (mut rest, mut twos, mut fives) = (den, 0_u32, 0_u32);
+    while rest % 2 == 0 {
+        rest /= 2;
+        twos += 1;
+    }
+    while rest % 5 == 0 {
+        rest /= 5;
+        fives += 1;
+    }
+    let exact_scale = twos.max(fives);
+    let scale = if rest == 1 && exact_scale <= MAX_UI_SCALE as u32 {
+        exact_scale as u8
+    } else {
+        MAX_UI_SCALE
+    };
+    let scaled = product(&[num, pow10(scale)?], "ratio")?;
+    Ok(UiAmount::new(div_round(scaled, den, Rounding::Nearest), scale).normalize())
+}
+
+/// returns both mantissas expressed at the larger of the two scales
+fn aligned(a: &UiAmount, b: &UiAmount) -> Option<(u128, u128, u8)> {
+    let scale = a.scale.max(b.scale);
+    let a_mantissa = a.rescale(scale, Rounding::Down).ok()?;
+    let b_mantissa = b.rescale(scale, Rounding::Down).ok()?;
+    Some((a_mantissa, b_mantissa, scale))
+}
+
+fn div_round(num: u128, den: u128, rounding: Rounding) -> u128 {
+    let (quotient, remainder) = (num / den, num % den);
+    let round_up = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => remainder > 0,
+        Rounding::Nearest => remainder > 0 && remainder >= den - remainder,
+    };
+    if round_up {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+fn pow10(exponent: u8) -> Result<u128> {
+    match 10_u128.checked_pow(exponent as u32) {
+        Some(power) => Ok(power),
+        None => Err(UtilsError::AmountOverflow("power of ten").into()),
+    }
+}
+
+fn product(factors: &[u128], what: &'static str) -> Result<u128> {
+    factors
+        .iter()
+        .try_fold(1_u128, |acc, factor| acc.checked_mul(*factor))
+        .ok_or_else(|| UtilsError::AmountOverflow(what).into())
+}
+
+fn to_u64(value: u128, what: &'static str) -> Result<u64> {
+    if value > u64::MAX as u128 {
+        return Err(UtilsError::AmountOverflow(what).into());
+    }
+    Ok(value as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sol_usdc() -> MarketSpec {
+        MarketSpec::new(100_000_000, 100, 9, 6)
+    }
+
+    #[test]
+    pub fn test_ui_amount_parse_and_display() {
+        let amount: UiAmount = "35.1230".parse().unwrap();
+        assert_eq!(amount, UiAmount::new(35_123, 3));
+        assert_eq!(amount.to_string(), "35.1230");
+        assert_eq!(amount.normalize().to_string(), "35.123");
+        assert_eq!(UiAmount::new(5, 3).to_string(), "0.005");
+        assert_eq!("7".parse::<UiAmount>().unwrap().to_string(), "7");
+        assert_eq!(".5".parse::<UiAmount>().unwrap(), UiAmount::new(5, 1));
+        assert!("".parse::<UiAmount>().is_err());
+        assert!("1.2.3".parse::<UiAmount>().is_err());
+        assert!("-1".parse::<UiAmount>().is_err());
+        assert_eq!(UiAmount::from_f64(0.1).unwrap(), UiAmount::new(1, 1));
+        assert!(UiAmount::from_f64(f64::NAN).is_err());
+    }
+
+    #[test]
+    pub fn test_ui_amount_arithmetic() {
+        let a = UiAmount::new(99_975, 3);
+        let b = UiAmount::new(5, 2);
+        assert_eq!(a.checked_add(&b).unwrap(), UiAmount::new(100_025, 3));
+        assert_eq!(a.checked_sub(&b).unwrap(), UiAmount::new(99_925, 3));
+        assert!(b.checked_sub(&a).is_none());
+        assert_eq!(a.checked_mul(&b).unwrap(), UiAmount::new(499_875, 5));
+        assert!(UiAmount::new(u128::MAX, 0)
+            .checked_add(&UiAmount::new(1, 0))
+            .is_none());
+    }
+
+    #[test]
+    pub fn test_native_conversions() {
+        let amount = UiAmount::new(12_345, 4);
+        assert_eq!(
+            amount.to_native(6, Rounding::Down).unwrap(),
+            NativeAmount(1_234_500)
+        );
+        assert_eq!(
+            amount.to_native(2, Rounding::Down).unwrap(),
+            NativeAmount(123)
+        );
+        assert_eq!(
+            amount.to_native(2, Rounding::Up).unwrap(),
+            NativeAmount(124)
+        );
+        assert_eq!(
+            amount.to_native(3, Rounding::Nearest).unwrap(),
+            NativeAmount(1_235)
+        );
+        assert_eq!(
+            UiAmount::from_native(NativeAmount(1_500_000_000), 9),
+            UiAmount::new(15, 1)
+        );
+    }
+
+    #[test]
+    pub fn test_market_spec_prices() {
+        let spec = sol_usdc();
+        assert_eq!(spec.tick_size().unwrap(), UiAmount::new(1, 3));
+        assert_eq!(
+            spec.lot_price_to_ui(LotPrice(35_123)).unwrap(),
+            UiAmount::new(35_123, 3)
+        );
+        let price: UiAmount = "35.123".parse().unwrap();
+        assert_eq!(
+            spec.ui_to_lot_price(&price, Rounding::Down).unwrap(),
+            LotPrice(35_123)
+        );
+        // off tick prices round as asked
+        let price: UiAmount = "35.1235".parse().unwrap();
+        assert_eq!(
+            spec.ui_to_lot_price(&price, Rounding::Down).unwrap(),
+            LotPrice(35_123)
+        );
+        assert_eq!(
+            spec.ui_to_lot_price(&price, Rounding::Nearest).unwrap(),
+            LotPrice(35_124)
+        );
+        // a tick of 1/3 has no exact decimal form
+        let spec = MarketSpec::new(3, 1, 0, 0);
+        assert_eq!(
+            spec.tick_size().unwrap(),
+            UiAmount::new(333_333_333_333_333_333, MAX_UI_SCALE)
+        );
+        assert_eq!(
+            spec.lot_price_to_ui(LotPrice(3)).unwrap(),
+            UiAmount::new(1, 0)
+        );
+    }
+
+    #[test]
+    pub fn test_market_spec_sizes() {
+        let spec = sol_usdc();
+        // 0.1 + 0.2 is 0.30000000000000004 as a float
+        let size = UiAmount::from_f64(0.1 + 0.2).unwrap();
+        assert_eq!(spec.ui_to_base_lots(&size, Rounding::Down).unwrap(), 3);
+        assert_eq!(spec.ui_to_base_lots(&size, Rounding::Up).unwrap(), 4);
+        assert_eq!(spec.base_lots_to_ui(3).unwrap(), UiAmount::new(3, 1));
+        assert_eq!(
+            spec.native_to_base_lots(NativeAmount(250_000_000), Rounding::Nearest)
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            spec.native_quote_qty(LotPrice(35_123), 3).unwrap(),
+            NativeAmount(10_536_900)
+        );
+        assert!(spec.base_lots_to_native(u64::MAX).is_err());
+        let zero_lots = MarketSpec::new(0, 100, 9, 6);
+        assert!(zero_lots.ui_to_base_lots(&size, Rounding::Down).is_err());
+        assert!(zero_lots
+            .native_to_base_lots(NativeAmount(1), Rounding::Down)
+            .is_err());
+        assert!(zero_lots.tick_size().is_err());
+    }
+}
@@ -6,6 +6,7 @@ pub const USDT_TOKEN_DECIMALS: u8 = 6;
 pub const WSOL_TOKEN_DECIMALS: u8 = 9;
 pub const RAY_TOKEN_DECIMALS: u8 = 6;
 pub const SRM_TOKEN_DECIMALS: u8 = 6;
+pub const MSRM_TOKEN_DECIMALS: u8 = 0;
 pub const RAY_USDC_LP_TOKEN_DECIMALS: u8 = 6;
 pub const RAY_USDT_LP_TOKEN_DECIMALS: u8 = 6;
 pub const RAY_SOL_LP_TOKEN_DECIMALS: u8 = 6;
@@ -34,6 +35,10 @@ pub fn srm_token_mint() -> Pubkey {
     *PUBKEY_MAP.get("srm_token_mint").unwrap()
 }
 
+pub fn msrm_token_mint() -> Pubkey {
+    *PUBKEY_MAP.get("msrm_token_mint").unwrap()
+}
+
 pub fn ray_usdc_lp_token_mint() -> Pubkey {
     *PUBKEY_MAP.get("ray_usdc_lp_token_mint").unwrap()
 }
@@ -86,14 +91,15 @@ impl Tokens {
 #[cfg(test)]
 mod tests {
     use crate::token_mints::{
-        RAY_SOL_LP_TOKEN_DECIMALS, RAY_SRM_LP_TOKEN_DECIMALS, RAY_TOKEN_DECIMALS,
-        RAY_USDC_LP_TOKEN_DECIMALS, RAY_USDT_LP_TOKEN_DECIMALS, SRM_TOKEN_DECIMALS,
-        USDC_TOKEN_DECIMALS, USDT_TOKEN_DECIMALS, WSOL_TOKEN_DECIMALS,
+        MSRM_TOKEN_DECIMALS, RAY_SOL_LP_TOKEN_DECIMALS, RAY_SRM_LP_TOKEN_DECIMALS,
+        RAY_TOKEN_DECIMALS, RAY_USDC_LP_TOKEN_DECIMALS, RAY_USDT_LP_TOKEN_DECIMALS,
+        SRM_TOKEN_DECIMALS, USDC_TOKEN_DECIMALS, USDT_TOKEN_DECIMALS, WSOL_TOKEN_DECIMALS,
     };
 
     use super::{
-        ray_sol_lp_token_mint, ray_srm_lp_token_mint, ray_token_mint, ray_usdc_lp_token_mint,
-        ray_usdt_lp_token_mint, srm_token_mint, usdc_token_mint, usdt_token_mint, wsol_token_mint,
+        msrm_token_mint, ray_sol_lp_token_mint, ray_srm_lp_token_mint, ray_token_mint,
+        ray_usdc_lp_token_mint, ray_usdt_lp_token_mint, srm_token_mint, usdc_token_mint,
+        usdt_token_mint, wsol_token_mint,
     };
 
     #[test]
@@ -103,6 +109,7 @@ mod tests {
         assert!(WSOL_TOKEN_DECIMALS == 9);
         assert!(RAY_TOKEN_DECIMALS == 6);
         assert!(SRM_TOKEN_DECIMALS == 6);
+        assert!(MSRM_TOKEN_DECIMALS == 0);
         assert!(RAY_USDC_LP_TOKEN_DECIMALS == 6);
         assert!(RAY_USDT_LP_TOKEN_DECIMALS == 6);
         assert!(RAY_SOL_LP_TOKEN_DECIMALS == 6);
@@ -116,6 +123,7 @@ mod tests {
         assert!(wsol_token_mint().to_string() == "So11111111111111111111111111111111111111112");
         assert!(ray_token_mint().to_string() == "4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R");
         assert!(srm_token_mint().to_string() == "SRMuApVNdxXokk5GT7XD5cUUgXMBCoAz2LHeuAoKWRt");
+        assert!(msrm_token_mint().to_string() == "MSRMcoVyrFxnSgo5uXwone5SKcGhT1KEJMFEkMEWf9L");
         assert!(
             ray_usdc_lp_token_mint().to_string() == "BZFGfXMrjG2sS7QT2eiCDEevPFnkYYF7kzJpWfYxPbcx"
         );